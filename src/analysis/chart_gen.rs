@@ -0,0 +1,269 @@
+use crate::types::{ArrowTimeToml, Directions, SongConfigToml, Speed};
+
+use super::decode::DecodedAudio;
+use super::loudness::loudness;
+use super::onset::{pick_onsets, spectral_flux, Onset};
+use super::tempo::estimate_beat_grid;
+
+/// 同一方向连续两个箭头的最小间隔（秒），小于它时换方向
+const JACK_INTERVAL: f64 = 0.3;
+/// 强度超过该值的起音在高难度下生成双押
+const CHORD_STRENGTH: f32 = 0.8;
+/// 生成双押的最低难度
+const CHORD_DIFFICULTY: u32 = 5;
+
+/// 自动生成谱面的参数
+#[derive(Debug, Copy, Clone)]
+pub struct GenerateOptions {
+    /// 难度（1 到 10），决定箭头密度
+    pub difficulty: u32,
+    /// 是否估计速度并把箭头对齐到节拍
+    pub snap_to_tempo: bool,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            difficulty: 5,
+            snap_to_tempo: true,
+        }
+    }
+}
+
+impl GenerateOptions {
+    /// 每秒最多箭头数
+    fn max_density(&self) -> f64 {
+        0.75 * self.difficulty.max(1).min(10) as f64 + 0.5
+    }
+    /// 对齐节拍时每拍的细分数
+    fn subdivision(&self) -> f64 {
+        if self.difficulty < 6 {
+            2.0
+        } else {
+            4.0
+        }
+    }
+    /// 箭头速度
+    fn speed(&self) -> Speed {
+        match self.difficulty {
+            0..=3 => Speed::Slow,
+            4..=7 => Speed::Medium,
+            _ => Speed::Fast,
+        }
+    }
+}
+
+/// 根据音频起音生成谱面草稿
+pub fn generate_chart(
+    name: String,
+    filename: String,
+    audio: &DecodedAudio,
+    options: &GenerateOptions,
+) -> SongConfigToml {
+    let envelope = spectral_flux(audio);
+    let mut onsets = pick_onsets(&envelope);
+    let beat_grid = estimate_beat_grid(&envelope);
+    if let Some(grid) = beat_grid.filter(|_| options.snap_to_tempo) {
+        let step = 60.0 / grid.bpm / options.subdivision();
+        onsets = snap_onsets(onsets, grid.offset, step);
+    }
+    let onsets = limit_density(onsets, 1.0 / options.max_density());
+    SongConfigToml {
+        name,
+        filename,
+        difficulty: Some(options.difficulty.max(1).min(10)),
+        bpm: beat_grid.map(|grid| grid.bpm),
+        offset: beat_grid.map(|grid| grid.offset),
+        loudness: loudness(audio),
+        arrows: assign_lanes(&onsets, options),
+        ..Default::default()
+    }
+}
+
+/// 对齐到以 origin 为原点的节拍网格，重合的只保留更强的
+fn snap_onsets(onsets: Vec<Onset>, origin: f64, step: f64) -> Vec<Onset> {
+    let mut snapped: Vec<Onset> = Vec::with_capacity(onsets.len());
+    for mut onset in onsets {
+        onset.time = origin + ((onset.time - origin) / step).round() * step;
+        match snapped.last_mut() {
+            Some(last) if (onset.time - last.time).abs() < step / 2.0 => {
+                if onset.strength > last.strength {
+                    *last = onset;
+                }
+            }
+            _ => snapped.push(onset),
+        }
+    }
+    snapped
+}
+
+/// 限制密度：优先保留强的起音，相邻箭头间隔不小于 min_gap
+fn limit_density(mut onsets: Vec<Onset>, min_gap: f64) -> Vec<Onset> {
+    onsets.sort_by(|a, b| b.strength.partial_cmp(&a.strength).unwrap());
+    let mut kept: Vec<Onset> = Vec::new();
+    for onset in onsets {
+        let index = kept.partition_point(|k| k.time < onset.time);
+        let too_close = |i: usize| {
+            kept.get(i)
+                .map_or(false, |k: &Onset| (k.time - onset.time).abs() < min_gap)
+        };
+        if too_close(index) || (index > 0 && too_close(index - 1)) {
+            continue;
+        }
+        kept.insert(index, onset);
+    }
+    kept
+}
+
+/// 分配方向：音色越高方向越靠上，避免快速连打同一方向，强拍加双押
+fn assign_lanes(onsets: &[Onset], options: &GenerateOptions) -> Vec<ArrowTimeToml> {
+    let directions = Directions::directions();
+    let lanes = directions.len();
+    // 按频谱质心排名，让各方向数量均匀
+    let mut centroids: Vec<f32> = onsets.iter().map(|o| o.centroid).collect();
+    centroids.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let speed = options.speed();
+    let mut arrows = Vec::new();
+    let mut last: Option<(f64, usize)> = None;
+    for onset in onsets {
+        let rank = centroids.partition_point(|&c| c < onset.centroid);
+        let quantile = rank as f64 / centroids.len() as f64;
+        let mut lane = (((1.0 - quantile) * lanes as f64) as usize).min(lanes - 1);
+        if let Some((last_time, last_lane)) = last {
+            if lane == last_lane && onset.time - last_time < JACK_INTERVAL {
+                lane = (lane + 1) % lanes;
+            }
+        }
+        let click_time = (onset.time * 1000.0).round() / 1000.0;
+        arrows.push(ArrowTimeToml {
+            click_time,
+            speed,
+            direction: directions[lane],
+            keysound: None,
+        });
+        if options.difficulty >= CHORD_DIFFICULTY && onset.strength >= CHORD_STRENGTH {
+            arrows.push(ArrowTimeToml {
+                click_time,
+                speed,
+                direction: directions[(lane + lanes / 2) % lanes],
+                keysound: None,
+            });
+        }
+        last = Some((onset.time, lane));
+    }
+    arrows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_audio::click_track;
+
+    fn onset(time: f64, strength: f32, centroid: f32) -> Onset {
+        Onset {
+            time,
+            strength,
+            centroid,
+        }
+    }
+
+    fn times(onsets: &[Onset]) -> Vec<f64> {
+        onsets.iter().map(|onset| onset.time).collect()
+    }
+
+    #[test]
+    fn snap_moves_onsets_to_the_grid() {
+        let onsets = vec![
+            onset(0.51, 0.5, 0.5),
+            onset(0.98, 0.5, 0.5),
+            // 和上一个对齐到同一格，只保留更强的
+            onset(1.04, 0.9, 0.5),
+            onset(1.27, 0.5, 0.5),
+        ];
+        let snapped = snap_onsets(onsets, 0.5, 0.25);
+        assert_eq!(times(&snapped), vec![0.5, 1.0, 1.25]);
+        assert!((snapped[1].strength - 0.9).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn density_depends_on_difficulty() {
+        // 每 0.1 秒一个起音，越靠后越强
+        let onsets: Vec<Onset> = (0..100)
+            .map(|i| onset(i as f64 * 0.1, i as f32 / 100.0, 0.5))
+            .collect();
+        let count = |difficulty| {
+            let options = GenerateOptions {
+                difficulty,
+                snap_to_tempo: false,
+            };
+            let min_gap = 1.0 / options.max_density();
+            let kept = limit_density(onsets.clone(), min_gap);
+            for pair in kept.windows(2) {
+                assert!(pair[1].time - pair[0].time >= min_gap - 1e-9);
+            }
+            kept.len()
+        };
+        let easy = count(1);
+        let hard = count(10);
+        assert!(easy < hard, "{} < {}", easy, hard);
+        assert!(easy as f64 <= 10.0 * 1.25 + 1.0);
+    }
+
+    #[test]
+    fn generated_arrows_follow_snap_option() {
+        // 第一拍不在帧的边界上，不对齐时箭头时间也不在网格上
+        let audio = click_track(120.0, 0.5, 8.0);
+        let chart = |snap_to_tempo| {
+            let options = GenerateOptions {
+                difficulty: 3,
+                snap_to_tempo,
+            };
+            generate_chart("test".into(), "test.mp3".into(), &audio, &options)
+        };
+        let on_grid = |chart: &SongConfigToml| {
+            let step = 60.0 / chart.bpm.unwrap() / 2.0;
+            let offset = chart.offset.unwrap();
+            chart.arrows.iter().all(|arrow| {
+                let steps = (arrow.click_time - offset) / step;
+                // 箭头时间保留到毫秒
+                (steps - steps.round()).abs() * step < 0.0015
+            })
+        };
+        let snapped = chart(true);
+        assert!(!snapped.arrows.is_empty());
+        assert!(on_grid(&snapped));
+        let unsnapped = chart(false);
+        assert!(!unsnapped.arrows.is_empty());
+        assert!(!on_grid(&unsnapped));
+    }
+
+    #[test]
+    fn lanes_never_overlap() {
+        // 强度和音色各不相同，高难度下会有双押
+        let onsets: Vec<Onset> = (0..200)
+            .map(|i| {
+                let strength = (i * 37 % 100) as f32 / 100.0;
+                let centroid = (i * 53 % 100) as f32 / 100.0;
+                onset(i as f64 * 0.125, strength, centroid)
+            })
+            .collect();
+        for difficulty in 1..=10 {
+            let options = GenerateOptions {
+                difficulty,
+                snap_to_tempo: true,
+            };
+            let arrows = assign_lanes(&onsets, &options);
+            assert!(arrows.len() >= onsets.len());
+            for (i, a) in arrows.iter().enumerate() {
+                for b in &arrows[i + 1..] {
+                    assert!(
+                        !((a.click_time - b.click_time).abs() < 1e-9 && a.direction == b.direction),
+                        "two notes at {} in {:?}",
+                        a.click_time,
+                        a.direction
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use rodio::{Decoder, Source};
+
+/// 解码后的单声道音频
+pub struct DecodedAudio {
+    /// 采样值（-1 到 1）
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+impl DecodedAudio {
+    /// 解码音频文件，多声道混合为单声道
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let decoder = Decoder::new(BufReader::new(file))
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let channels = decoder.channels().max(1) as usize;
+        let sample_rate = decoder.sample_rate();
+        let interleaved: Vec<i16> = decoder.collect();
+        let samples = interleaved
+            .chunks(channels)
+            .map(|frame| {
+                let sum: f32 = frame.iter().map(|&s| s as f32 / i16::MAX as f32).sum();
+                sum / frame.len() as f32
+            })
+            .collect();
+        Ok(Self {
+            samples,
+            sample_rate,
+        })
+    }
+}
@@ -0,0 +1,37 @@
+use super::decode::DecodedAudio;
+
+/// 计算响度的块长度（秒）
+const BLOCK_SECONDS: f64 = 0.4;
+/// 低于该响度（dB）的块视为静音
+const ABSOLUTE_GATE: f64 = -70.0;
+/// 比平均响度低这么多（dB）的块不参与计算，避免安静段落拉低结果
+const RELATIVE_GATE: f64 = -10.0;
+
+/// 歌曲的整体响度（dBFS），类似 LUFS 的门限平均，没有频率加权
+/// 全部静音时为 None
+pub fn loudness(audio: &DecodedAudio) -> Option<f64> {
+    let block = ((audio.sample_rate as f64 * BLOCK_SECONDS) as usize).max(1);
+    let powers: Vec<f64> = audio
+        .samples
+        .chunks(block)
+        .map(|chunk| {
+            chunk.iter().map(|&s| (s as f64) * (s as f64)).sum::<f64>() / chunk.len() as f64
+        })
+        .filter(|&power| to_db(power) > ABSOLUTE_GATE)
+        .collect();
+    if powers.is_empty() {
+        return None;
+    }
+    let mean = powers.iter().sum::<f64>() / powers.len() as f64;
+    let threshold = to_db(mean) + RELATIVE_GATE;
+    let loud: Vec<f64> = powers
+        .into_iter()
+        .filter(|&power| to_db(power) > threshold)
+        .collect();
+    Some(to_db(loud.iter().sum::<f64>() / loud.len() as f64))
+}
+
+/// 平均功率转换为 dB
+fn to_db(power: f64) -> f64 {
+    10.0 * power.max(1e-12).log10()
+}
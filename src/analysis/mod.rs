@@ -0,0 +1,19 @@
+//! 音频分析：解码、起音检测、速度估计、响度、自动生成谱面
+
+pub use chart_gen::*;
+pub use decode::*;
+pub use loudness::*;
+pub use onset::*;
+pub use tempo::*;
+pub use waveform::*;
+
+mod chart_gen;
+mod decode;
+mod fft;
+mod loudness;
+mod onset;
+mod tempo;
+mod waveform;
+
+#[cfg(test)]
+mod test_audio;
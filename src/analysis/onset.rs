@@ -0,0 +1,142 @@
+use super::decode::DecodedAudio;
+use super::fft::magnitude_spectrum;
+
+/// 分析帧长度（采样数）
+pub const FRAME_SIZE: usize = 1024;
+/// 帧移（采样数）
+pub const HOP_SIZE: usize = 512;
+
+/// 阈值的滑动窗口半径（帧）
+const THRESHOLD_RADIUS: usize = 8;
+/// 阈值 = 局部均值 * 倍数 + 偏移
+const THRESHOLD_MULTIPLIER: f32 = 1.4;
+const THRESHOLD_OFFSET: f32 = 0.02;
+/// 相邻起音的最小间隔（秒）
+const MIN_ONSET_INTERVAL: f64 = 0.05;
+
+/// 起音强度包络（每帧的频谱通量）
+pub struct OnsetEnvelope {
+    /// 归一化到 0 到 1 的频谱通量
+    pub flux: Vec<f32>,
+    /// 每帧的频谱质心（0 到 1，越大音色越高）
+    pub centroid: Vec<f32>,
+    /// 每秒帧数
+    pub frame_rate: f64,
+}
+
+impl OnsetEnvelope {
+    /// 帧下标对应的时间（秒）
+    pub fn time_of(&self, frame: usize) -> f64 {
+        frame as f64 / self.frame_rate
+    }
+}
+
+/// 一个起音
+#[derive(Debug, Copy, Clone)]
+pub struct Onset {
+    /// 时间（秒）
+    pub time: f64,
+    /// 强度（0 到 1）
+    pub strength: f32,
+    /// 频谱质心（0 到 1）
+    pub centroid: f32,
+}
+
+/// 计算频谱通量：相邻帧对数幅度谱的正向增量之和
+pub fn spectral_flux(audio: &DecodedAudio) -> OnsetEnvelope {
+    let mut flux = Vec::new();
+    let mut centroid = Vec::new();
+    let mut last_spectrum: Option<Vec<f32>> = None;
+    let mut start = 0;
+    while start + FRAME_SIZE <= audio.samples.len() {
+        let spectrum: Vec<f32> = magnitude_spectrum(&audio.samples[start..start + FRAME_SIZE])
+            .iter()
+            .map(|m| (1.0 + 100.0 * m).ln())
+            .collect();
+        let value = match &last_spectrum {
+            Some(last) => spectrum
+                .iter()
+                .zip(last.iter())
+                .map(|(cur, last)| (cur - last).max(0.0))
+                .sum(),
+            None => 0.0,
+        };
+        let total: f32 = spectrum.iter().sum();
+        let weighted: f32 = spectrum.iter().enumerate().map(|(i, m)| i as f32 * m).sum();
+        flux.push(value);
+        centroid.push(if total > 0.0 {
+            weighted / total / spectrum.len() as f32
+        } else {
+            0.0
+        });
+        last_spectrum = Some(spectrum);
+        start += HOP_SIZE;
+    }
+    // 归一化
+    let max = flux.iter().cloned().fold(0.0, f32::max);
+    if max > 0.0 {
+        flux.iter_mut().for_each(|v| *v /= max);
+    }
+    OnsetEnvelope {
+        flux,
+        centroid,
+        frame_rate: audio.sample_rate as f64 / HOP_SIZE as f64,
+    }
+}
+
+/// 从包络中挑选起音：超过自适应阈值的局部最大值
+pub fn pick_onsets(envelope: &OnsetEnvelope) -> Vec<Onset> {
+    let flux = &envelope.flux;
+    let mut onsets: Vec<Onset> = Vec::new();
+    for i in 0..flux.len() {
+        let from = i.saturating_sub(THRESHOLD_RADIUS);
+        let to = (i + THRESHOLD_RADIUS + 1).min(flux.len());
+        let window = &flux[from..to];
+        let mean = window.iter().sum::<f32>() / window.len() as f32;
+        let threshold = mean * THRESHOLD_MULTIPLIER + THRESHOLD_OFFSET;
+        let is_peak = window.iter().all(|&v| v <= flux[i]);
+        if !is_peak || flux[i] < threshold {
+            continue;
+        }
+        let onset = Onset {
+            time: envelope.time_of(i),
+            strength: flux[i],
+            centroid: envelope.centroid[i],
+        };
+        match onsets.last_mut() {
+            // 间隔太近只保留更强的
+            Some(last) if onset.time - last.time < MIN_ONSET_INTERVAL => {
+                if onset.strength > last.strength {
+                    *last = onset;
+                }
+            }
+            _ => onsets.push(onset),
+        }
+    }
+    onsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_audio::click_track;
+
+    #[test]
+    fn finds_every_click() {
+        let audio = click_track(120.0, 0.5, 6.0);
+        let onsets = pick_onsets(&spectral_flux(&audio));
+        let clicks: Vec<f64> = (0..11).map(|beat| 0.5 + beat as f64 * 0.5).collect();
+        assert_eq!(onsets.len(), clicks.len());
+        for (onset, click) in onsets.iter().zip(clicks.iter()) {
+            // 起音按分析帧的开始计时，最多早一帧
+            let early = click - onset.time;
+            assert!((0.0..0.03).contains(&early), "{} vs {}", onset.time, click);
+        }
+    }
+
+    #[test]
+    fn silence_has_no_onsets() {
+        let audio = click_track(120.0, 10.0, 2.0);
+        assert!(pick_onsets(&spectral_flux(&audio)).is_empty());
+    }
+}
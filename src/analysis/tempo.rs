@@ -0,0 +1,191 @@
+use super::decode::DecodedAudio;
+use super::onset::{spectral_flux, OnsetEnvelope, FRAME_SIZE, HOP_SIZE};
+
+/// 估计范围内的最小 BPM
+pub const MIN_BPM: f64 = 60.0;
+/// 估计范围内的最大 BPM
+pub const MAX_BPM: f64 = 200.0;
+
+/// 半拍处自相关与峰值之比超过该值时取双倍速度
+const OCTAVE_RATIO: f32 = 0.5;
+
+/// 细化周期时在粗略周期前后各搜索的步数（每步 1/PERIOD_STEPS 帧）
+const PERIOD_STEPS: i32 = 50;
+
+/// 节拍分析结果
+#[derive(Debug, Copy, Clone)]
+pub struct BeatGrid {
+    pub bpm: f64,
+    /// 第一拍的时间（秒）
+    pub offset: f64,
+    /// 置信度（0 到 1）
+    pub confidence: f32,
+}
+
+/// 分析音频的 BPM 和第一拍位置
+pub fn analyze_beat_grid(audio: &DecodedAudio) -> Option<BeatGrid> {
+    estimate_beat_grid(&spectral_flux(audio))
+}
+
+/// 由起音包络估计 BPM 和第一拍位置
+pub fn estimate_beat_grid(envelope: &OnsetEnvelope) -> Option<BeatGrid> {
+    let flux = &envelope.flux;
+    let min_lag = (envelope.frame_rate * 60.0 / MAX_BPM).floor() as usize;
+    let max_lag = (envelope.frame_rate * 60.0 / MIN_BPM).ceil() as usize;
+    if min_lag <= 1 || max_lag + 1 >= flux.len() {
+        return None;
+    }
+    // 包络的自相关，峰值所在的延迟就是一拍的长度
+    let mean = flux.iter().sum::<f32>() / flux.len() as f32;
+    let centered: Vec<f32> = flux.iter().map(|v| v - mean).collect();
+    let autocorrelation = |lag: usize| -> f32 {
+        centered
+            .iter()
+            .zip(centered[lag..].iter())
+            .map(|(a, b)| a * b)
+            .sum()
+    };
+    let (mut best_lag, mut best_score) = (min_lag..=max_lag)
+        .map(|lag| (lag, autocorrelation(lag)))
+        .fold(
+            (0, 0.0),
+            |best, cur| if cur.1 > best.1 { cur } else { best },
+        );
+    if best_score <= 0.0 {
+        return None;
+    }
+    // 半拍处也有明显的峰值时取更快的速度，避免估计成一半 BPM
+    while best_lag / 2 >= min_lag {
+        let (half_lag, half_score) = (best_lag / 2..=(best_lag + 1) / 2)
+            .map(|lag| (lag, autocorrelation(lag)))
+            .fold(
+                (0, 0.0),
+                |best, cur| if cur.1 > best.1 { cur } else { best },
+            );
+        if half_score < best_score * OCTAVE_RATIO {
+            break;
+        }
+        best_lag = half_lag;
+        best_score = half_score;
+    }
+    // 在粗略周期附近细化周期和相位：节拍网格上的平均通量最大
+    let comb_mean = |period: f64, phase: usize| -> f32 {
+        let mut sum = 0.0;
+        let mut count = 0;
+        let mut frame = phase as f64;
+        while (frame.round() as usize) < flux.len() {
+            sum += flux[frame.round() as usize];
+            count += 1;
+            frame += period;
+        }
+        sum / count.max(1) as f32
+    };
+    let mut best = (best_lag as f64, 0, 0.0);
+    for step in -PERIOD_STEPS..=PERIOD_STEPS {
+        let period = best_lag as f64 + step as f64 / PERIOD_STEPS as f64;
+        for phase in 0..period.ceil() as usize {
+            let score = comb_mean(period, phase);
+            if score > best.2 {
+                best = (period, phase, score);
+            }
+        }
+    }
+    let (period, phase, score) = best;
+    // 置信度：节拍位置上的通量相对整体均值的突出程度
+    let confidence = if score > 0.0 {
+        (score - mean) / score
+    } else {
+        0.0
+    };
+    let (period, first_beat) = fit_beats(flux, period, phase);
+    // 帧的时间是帧的开始，分析窗口的中心晚半帧
+    let latency = FRAME_SIZE as f64 / 2.0 / HOP_SIZE as f64;
+    Some(BeatGrid {
+        bpm: 60.0 * envelope.frame_rate / period,
+        offset: (first_beat + latency) / envelope.frame_rate,
+        confidence: confidence.max(0.0).min(1.0),
+    })
+}
+
+/// 在每拍附近找通量的峰值（抛物线插值到帧以下），按通量加权拟合直线，
+/// 返回细化后的周期和第一拍所在的帧；峰值太少或拟合偏离太多时保持原样
+fn fit_beats(flux: &[f32], period: f64, phase: usize) -> (f64, f64) {
+    let mut peaks = Vec::new();
+    let mut beat = 0;
+    loop {
+        let center = (phase as f64 + beat as f64 * period).round() as usize;
+        if center + 2 >= flux.len() {
+            break;
+        }
+        let from = center.saturating_sub(1).max(1);
+        let peak = (from..=center + 1)
+            .max_by(|&a, &b| flux[a].partial_cmp(&flux[b]).unwrap())
+            .unwrap();
+        let (before, value, after) = (flux[peak - 1], flux[peak], flux[peak + 1]);
+        let curvature = before - 2.0 * value + after;
+        let shift = if curvature < 0.0 {
+            (0.5 * (before - after) / curvature).max(-0.5).min(0.5)
+        } else {
+            0.0
+        };
+        peaks.push((beat as f64, peak as f64 + shift as f64, value as f64));
+        beat += 1;
+    }
+    let weight: f64 = peaks.iter().map(|(_, _, w)| w).sum();
+    if peaks.len() < 2 || weight <= 0.0 {
+        return (period, phase as f64);
+    }
+    let mean_beat = peaks.iter().map(|(k, _, w)| k * w).sum::<f64>() / weight;
+    let mean_frame = peaks.iter().map(|(_, f, w)| f * w).sum::<f64>() / weight;
+    let covariance: f64 = peaks
+        .iter()
+        .map(|(k, f, w)| w * (k - mean_beat) * (f - mean_frame))
+        .sum();
+    let variance: f64 = peaks
+        .iter()
+        .map(|(k, _, w)| w * (k - mean_beat).powi(2))
+        .sum();
+    if variance <= 0.0 {
+        return (period, phase as f64);
+    }
+    let fitted = covariance / variance;
+    if (fitted - period).abs() > 1.0 {
+        return (period, phase as f64);
+    }
+    (fitted, mean_frame - fitted * mean_beat)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_audio::click_track;
+
+    fn assert_grid(bpm: f64, first_beat: f64) {
+        let grid = analyze_beat_grid(&click_track(bpm, first_beat, 12.0)).unwrap();
+        assert!((grid.bpm - bpm).abs() < 0.05, "bpm {} vs {}", grid.bpm, bpm);
+        // 网格可能从更早的一拍算起
+        let beat = 60.0 / bpm;
+        let error = (grid.offset - first_beat) / beat;
+        let error = (error - error.round()) * beat;
+        assert!(
+            error.abs() < 0.004,
+            "offset {} vs {}",
+            grid.offset,
+            first_beat
+        );
+        assert!(grid.confidence > 0.8, "confidence {}", grid.confidence);
+    }
+
+    #[test]
+    fn click_track_grid() {
+        assert_grid(120.0, 0.5);
+        assert_grid(128.0, 0.3);
+        assert_grid(90.0, 0.25);
+    }
+
+    #[test]
+    fn silence_has_no_grid() {
+        let audio = click_track(120.0, 20.0, 12.0);
+        assert!(analyze_beat_grid(&audio).map_or(true, |grid| grid.confidence < 0.5));
+    }
+}
@@ -0,0 +1,31 @@
+//! 测试用的合成音频
+
+use super::DecodedAudio;
+
+pub const SAMPLE_RATE: u32 = 44100;
+/// 每个click的长度（秒）
+const CLICK_LENGTH: f64 = 0.01;
+
+/// 从 first_beat 秒开始每拍一个click，共 seconds 秒
+pub fn click_track(bpm: f64, first_beat: f64, seconds: f64) -> DecodedAudio {
+    let mut samples = vec![0.0; (seconds * SAMPLE_RATE as f64) as usize];
+    let beat = 60.0 / bpm;
+    let click_samples = (CLICK_LENGTH * SAMPLE_RATE as f64) as usize;
+    // 固定种子的噪声，结果可以重现
+    let mut seed = 0x2545_f491u32;
+    let mut time = first_beat;
+    while time < seconds {
+        let start = (time * SAMPLE_RATE as f64).round() as usize;
+        for i in 0..click_samples.min(samples.len().saturating_sub(start)) {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let noise = (seed >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+            let decay = 1.0 - i as f32 / click_samples as f32;
+            samples[start + i] = noise * decay * 0.8;
+        }
+        time += beat;
+    }
+    DecodedAudio {
+        samples,
+        sample_rate: SAMPLE_RATE,
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::decode::DecodedAudio;
+use super::fft::magnitude_spectrum;
+
+/// 每秒的峰值列数
+pub const PEAKS_PER_SECOND: u32 = 100;
+/// 频谱图的频带数
+pub const SPECTRUM_BANDS: usize = 32;
+/// 波形缓存目录
+const CACHE_DIR: &str = "cache/waveforms";
+/// 缓存文件头
+const CACHE_MAGIC: &[u8; 4] = b"RWF1";
+/// 计算频谱时的帧长度
+const SPECTRUM_FRAME: usize = 1024;
+
+/// 波形峰值和频谱图，每列对应 1 / PEAKS_PER_SECOND 秒，值量化为 0 到 255
+pub struct WaveformPeaks {
+    pub peaks: Vec<u8>,
+    /// 每列 SPECTRUM_BANDS 个频带，低频在前
+    pub spectrum: Vec<u8>,
+}
+
+impl WaveformPeaks {
+    /// 由解码后的音频计算
+    pub fn compute(audio: &DecodedAudio) -> Self {
+        let hop = (audio.sample_rate / PEAKS_PER_SECOND).max(1) as usize;
+        let columns = audio.samples.len() / hop;
+        let mut peaks = Vec::with_capacity(columns);
+        let mut spectrum = Vec::with_capacity(columns * SPECTRUM_BANDS);
+        let mut frame = vec![0.0; SPECTRUM_FRAME];
+        for column in 0..columns {
+            let start = column * hop;
+            let peak = audio.samples[start..start + hop]
+                .iter()
+                .fold(0.0f32, |max, s| max.max(s.abs()));
+            peaks.push(quantize(peak));
+            // 以该列为中心取一帧计算频谱
+            let from = (start + hop / 2).saturating_sub(SPECTRUM_FRAME / 2);
+            frame.iter_mut().enumerate().for_each(|(i, v)| {
+                *v = audio.samples.get(from + i).cloned().unwrap_or(0.0);
+            });
+            let magnitudes = magnitude_spectrum(&frame);
+            spectrum.extend(band_levels(&magnitudes).iter().map(|&v| quantize(v)));
+        }
+        Self { peaks, spectrum }
+    }
+    /// 优先读取缓存，没有缓存时解码音频并写入缓存
+    pub fn load_or_compute(audio_path: &Path) -> io::Result<Self> {
+        let cache_path = cache_path(audio_path)?;
+        if let Ok(peaks) = Self::read(&cache_path) {
+            return Ok(peaks);
+        }
+        let peaks = Self::compute(&DecodedAudio::load(audio_path)?);
+        // 写缓存失败不影响使用
+        if let Err(e) = peaks.write(&cache_path) {
+            eprintln!("couldn't write {}: {}", cache_path.display(), e);
+        }
+        Ok(peaks)
+    }
+    /// 列数
+    pub fn columns(&self) -> usize {
+        self.peaks.len()
+    }
+    /// 歌曲长度（秒）
+    pub fn duration(&self) -> f64 {
+        self.columns() as f64 / PEAKS_PER_SECOND as f64
+    }
+    /// time 时刻的列下标
+    pub fn column_at(&self, time: f64) -> Option<usize> {
+        let column = time * PEAKS_PER_SECOND as f64;
+        if column >= 0.0 && (column as usize) < self.columns() {
+            Some(column as usize)
+        } else {
+            None
+        }
+    }
+    /// 长度为 length 秒的最响片段的开始时间，歌曲比片段短时为 0
+    pub fn loudest_section(&self, length: f64) -> f64 {
+        let window = ((length * PEAKS_PER_SECOND as f64) as usize).max(1);
+        if self.columns() <= window {
+            return 0.0;
+        }
+        let mut sum: u32 = self.peaks[..window].iter().map(|&p| p as u32).sum();
+        let (mut best, mut best_sum) = (0, sum);
+        for start in 1..=self.columns() - window {
+            sum = sum + self.peaks[start + window - 1] as u32 - self.peaks[start - 1] as u32;
+            if sum > best_sum {
+                best = start;
+                best_sum = sum;
+            }
+        }
+        best as f64 / PEAKS_PER_SECOND as f64
+    }
+    /// 某一列的频带
+    pub fn bands(&self, column: usize) -> &[u8] {
+        &self.spectrum[column * SPECTRUM_BANDS..(column + 1) * SPECTRUM_BANDS]
+    }
+
+    fn read(path: &Path) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        let field = |i: usize| {
+            let mut bytes = [0; 4];
+            bytes.copy_from_slice(&header[i..i + 4]);
+            u32::from_le_bytes(bytes)
+        };
+        if &header[..4] != CACHE_MAGIC || field(4) != PEAKS_PER_SECOND {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad cache"));
+        }
+        let columns = field(8) as usize;
+        let mut peaks = vec![0; columns];
+        let mut spectrum = vec![0; columns * SPECTRUM_BANDS];
+        reader.read_exact(&mut peaks)?;
+        reader.read_exact(&mut spectrum)?;
+        Ok(Self { peaks, spectrum })
+    }
+
+    fn write(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(CACHE_MAGIC)?;
+        writer.write_all(&PEAKS_PER_SECOND.to_le_bytes())?;
+        writer.write_all(&(self.columns() as u32).to_le_bytes())?;
+        writer.write_all(&self.peaks)?;
+        writer.write_all(&self.spectrum)?;
+        writer.flush()
+    }
+}
+
+/// 缓存文件路径：由音频路径、大小和修改时间决定，音频变化后自动失效
+fn cache_path(audio_path: &Path) -> io::Result<PathBuf> {
+    let metadata = fs::metadata(audio_path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let mut hasher = DefaultHasher::new();
+    audio_path.hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    modified.hash(&mut hasher);
+    Ok(Path::new(CACHE_DIR).join(format!("{:016x}.peaks", hasher.finish())))
+}
+
+/// 幅度谱按对数间隔分成若干频带，返回每个频带 0 到 1 的响度
+fn band_levels(magnitudes: &[f32]) -> [f32; SPECTRUM_BANDS] {
+    let mut levels = [0.0; SPECTRUM_BANDS];
+    let bins = magnitudes.len() as f32;
+    for (band, level) in levels.iter_mut().enumerate() {
+        let from = bins.powf(band as f32 / SPECTRUM_BANDS as f32) as usize;
+        let to = (bins.powf((band + 1) as f32 / SPECTRUM_BANDS as f32) as usize).max(from + 1);
+        let slice = &magnitudes[from.min(magnitudes.len() - 1)..to.min(magnitudes.len())];
+        let mean = slice.iter().sum::<f32>() / slice.len().max(1) as f32;
+        // 对数压缩
+        *level = ((1.0 + mean).ln() / 6.0).min(1.0);
+    }
+    levels
+}
+
+fn quantize(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0) as u8
+}
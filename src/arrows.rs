@@ -1,29 +1,50 @@
 use bevy::prelude::*;
 
 use crate::consts::*;
-use crate::score::ScoreResource;
+use crate::mods::VisibilityMod;
+use crate::score::{Judgement, ScoreResource};
 use crate::time::ControlledTime;
 use crate::types::*;
 use crate::AppState;
 
+/// 箭头的生成、移动和判定，不需要窗口和图片，可以在模拟中运行
 pub struct ArrowsPlugin;
 
 impl Plugin for ArrowsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<CorrectArrowEvent>()
+            .add_event::<MissArrowEvent>()
+            .add_event::<SongFinishedEvent>()
+            .add_system_set(
+                // 每帧先更新时间，判定结果只取决于时间和输入
+                SystemSet::on_update(AppState::Game)
+                    .after("update_time")
+                    .with_system(detect_song_end.system().before("spawn_arrows"))
+                    .with_system(spawn_arrows.system().label("spawn_arrows")) // 生成箭头
+                    .with_system(move_arrows.system().label("move_arrows")) // 箭头移动
+                    .with_system(despawn_arrows.system().label("judge").after("move_arrows")), // 判定并移除箭头
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Game).with_system(despawn_all_arrows.system()),
+            );
+    }
+}
+
+/// 箭头和目标箭头的图片
+pub struct ArrowSpritesPlugin;
+
+impl Plugin for ArrowSpritesPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ArrowMaterialResource>()
-            .add_event::<CorrectArrowEvent>()
             .add_system_set(
                 SystemSet::on_enter(AppState::Game).with_system(setup_target_arrows.system()),
             )
             .add_system_set(
                 SystemSet::on_update(AppState::Game)
-                    .with_system(spawn_arrows.system()) // 生成箭头
-                    .with_system(move_arrows.system()) // 箭头移动
-                    .with_system(despawn_arrows.system()), // 移除箭头
+                    .with_system(apply_lane_cover.system().after("move_arrows")), // 遮挡箭头
             )
-            .add_system_set(
-                SystemSet::on_exit(AppState::Game).with_system(despawn_all_arrows.system()),
-            );
+            // 生成的箭头在同一帧加上图片，已经移除的箭头不会再加
+            .add_system_to_stage(CoreStage::PostUpdate, add_arrow_sprites.system());
     }
 }
 
@@ -33,6 +54,19 @@ struct ArrowMaterialResource {
     blue_texture: Handle<ColorMaterial>,
     green_texture: Handle<ColorMaterial>,
     border_texture: Handle<ColorMaterial>,
+    /// 遮挡箭头时使用的半透明材质，按速度和透明度分级共用
+    faded: [Vec<Handle<ColorMaterial>>; 3],
+}
+
+/// 半透明材质的透明度分级数
+const ALPHA_LEVELS: usize = 20;
+
+impl ArrowMaterialResource {
+    /// 这个速度的箭头在这个透明度下使用的材质
+    fn faded(&self, speed: Speed, alpha: f32) -> Handle<ColorMaterial> {
+        let level = (alpha * ALPHA_LEVELS as f32).round() as usize;
+        self.faded[speed as usize][level.min(ALPHA_LEVELS)].clone()
+    }
 }
 
 impl FromWorld for ArrowMaterialResource {
@@ -46,37 +80,66 @@ impl FromWorld for ArrowMaterialResource {
         let blue_handle = asset_server.load("images/arrow_blue.png");
         let green_handle = asset_server.load("images/arrow_green.png");
         let border_handle = asset_server.load("images/arrow_border.png");
+        let mut faded = |texture: &Handle<Texture>| {
+            (0..=ALPHA_LEVELS)
+                .map(|level| {
+                    materials.add(ColorMaterial {
+                        color: Color::rgba(1.0, 1.0, 1.0, level as f32 / ALPHA_LEVELS as f32),
+                        texture: Some(texture.clone()),
+                    })
+                })
+                .collect()
+        };
+        let faded = [
+            faded(&red_handle),
+            faded(&blue_handle),
+            faded(&green_handle),
+        ];
         ArrowMaterialResource {
             red_texture: materials.add(red_handle.into()),
             blue_texture: materials.add(blue_handle.into()),
             green_texture: materials.add(green_handle.into()),
             border_texture: materials.add(border_handle.into()),
+            faded,
         }
     }
 }
 
 /// 箭头组件
-struct Arrow {
+pub struct Arrow {
     speed: Speed,
+    /// 高速倍数，判定范围随之放大，保持判定时间不变
+    hi_speed: f32,
     direction: Directions,
+    keysound: Option<Handle<AudioSource>>,
+    player: Player,
 }
 
 impl Arrow {
     fn from(arrow_time: &ArrowTime) -> Self {
         Self {
             speed: arrow_time.speed,
+            hi_speed: arrow_time.hi_speed,
             direction: arrow_time.direction,
+            keysound: arrow_time.keysound.clone(),
+            player: arrow_time.player,
         }
     }
+    fn velocity(&self) -> f32 {
+        self.speed.value() * self.hi_speed
+    }
+    /// 判定范围（像素）
+    fn threshold(&self) -> f32 {
+        THRESHOLD * self.hi_speed
+    }
 }
 
-/// 生成箭头
-fn spawn_arrows(
-    mut cmd: Commands,
-    mut song_config: ResMut<SongConfig>,
-    materials: Res<ArrowMaterialResource>,
-    time: Res<ControlledTime>,
-) {
+/// 生成箭头，图片由 add_arrow_sprites 加上
+fn spawn_arrows(mut cmd: Commands, mut song_config: ResMut<SongConfig>, time: Res<ControlledTime>) {
+    // 失败后不再生成
+    if song_config.finished {
+        return;
+    }
     // 从启动到现在的时间（减3 是因为歌曲在游戏开始3秒后播放）
     let sec = time.seconds_since_startup() - DELAY_SONG;
     // 两帧时间差
@@ -87,18 +150,18 @@ fn spawn_arrows(
     let mut remove_counter = 0;
     for arrow in &song_config.arrows {
         // 如果生成时间介于上一帧和这一帧之间
-        // println!("sec:{}, delta:{}, sec_last:{}, spawn_time:{}", sec, delta, sec_last, arrow.spawn_time);
         if arrow.spawn_time > sec_last && arrow.spawn_time <= sec {
             remove_counter += 1;
 
-            // 根据速度获取材质
-            let material = match arrow.speed {
-                Speed::Slow => materials.red_texture.clone(),
-                Speed::Medium => materials.blue_texture.clone(),
-                Speed::Fast => materials.green_texture.clone(),
-            };
-            let sprite_bundle = spawn_arrow_sprite(material, &arrow.direction, SPAWN_POSITION);
-            cmd.spawn_bundle(sprite_bundle).insert(Arrow::from(arrow));
+            // 生成时间在两帧之间，补上生成之后已经走过的距离，保证按时到达目标
+            let component = Arrow::from(arrow);
+            let x = SPAWN_POSITION + (sec - arrow.spawn_time) as f32 * component.velocity();
+            let y = arrow.player.lane_y(arrow.direction, song_config.versus);
+            let transform = arrow_transform(&arrow.direction, Vec2::new(x, y));
+            cmd.spawn()
+                .insert(transform)
+                .insert(GlobalTransform::from(transform))
+                .insert(component);
         } else {
             break;
         }
@@ -109,40 +172,71 @@ fn spawn_arrows(
     }
 }
 
-/// 删除箭头
+/// 所有箭头都已击中或错过时歌曲结束
+/// 在生成之前检查，保证上一帧生成的箭头已经加入
+fn detect_song_end(
+    mut song_config: ResMut<SongConfig>,
+    arrows: Query<(), With<Arrow>>,
+    mut finished_event: EventWriter<SongFinishedEvent>,
+) {
+    if !song_config.finished && song_config.arrows.is_empty() && arrows.iter().next().is_none() {
+        song_config.finished = true;
+        finished_event.send(SongFinishedEvent);
+    }
+}
+
+/// 判定并删除箭头，分数根据判定事件计算
 fn despawn_arrows(
     mut cmd: Commands,
+    song_config: Res<SongConfig>,
     arrows: Query<(Entity, &Transform, &Arrow)>,
     key_input: Res<Input<KeyCode>>,
-    mut score: ResMut<ScoreResource>,
     mut correct_event: EventWriter<CorrectArrowEvent>,
+    mut miss_event: EventWriter<MissArrowEvent>,
 ) {
+    // 失败后不再判定，剩下的箭头直接飞出去
+    if song_config.finished {
+        return;
+    }
     arrows.for_each(|(entity, transform, arrow)| {
         let pos = transform.translation.x;
         // 检测箭头是否在目标箭头范围内被点击
-        if (TARGET_POSITION - THRESHOLD..=TARGET_POSITION + THRESHOLD).contains(&pos)
-            && arrow.direction.key_just_pressed(&key_input)
+        let threshold = arrow.threshold();
+        if (TARGET_POSITION - threshold..=TARGET_POSITION + threshold).contains(&pos)
+            && arrow
+                .player
+                .key_just_pressed(arrow.direction, &key_input, song_config.versus)
         {
-            let points = score.increase_correct(TARGET_POSITION - pos);
+            // 按正常速度时的距离判定
+            let distance = (TARGET_POSITION - pos) / arrow.hi_speed;
             cmd.entity(entity).despawn();
             correct_event.send(CorrectArrowEvent {
                 direction: arrow.direction,
-                points,
+                player: arrow.player,
+                distance,
+                points: ScoreResource::points(distance),
+                judgement: Judgement::from_distance(distance),
+                // 还没到目标位置时按下是提前
+                offset: -distance / arrow.speed.value(),
+                keysound: arrow.keysound.clone(),
             });
         }
         // 是否离开屏幕
         if pos >= 2.0 * TARGET_POSITION {
             cmd.entity(entity).despawn();
-            score.increase_fails();
+            miss_event.send(MissArrowEvent {
+                direction: arrow.direction,
+                player: arrow.player,
+            });
         }
     });
 }
 /// 移动箭头
 fn move_arrows(time: Res<ControlledTime>, arrows: Query<(&mut Transform, &Arrow)>) {
     arrows.for_each_mut(|(mut transform, arrow)| {
-        transform.translation.x += time.delta_seconds() * arrow.speed.value();
+        transform.translation.x += time.delta_seconds() * arrow.velocity();
 
-        let distance_after_target = transform.translation.x - (TARGET_POSITION + THRESHOLD);
+        let distance_after_target = transform.translation.x - (TARGET_POSITION + arrow.threshold());
         if distance_after_target >= 0.02 {
             // 下移
             transform.translation.y -= time.delta_seconds() * distance_after_target * 2.0;
@@ -157,34 +251,109 @@ fn move_arrows(time: Res<ControlledTime>, arrows: Query<(&mut Transform, &Arrow)
     });
 }
 
+/// 给新生成的箭头加上图片
+fn add_arrow_sprites(
+    mut cmd: Commands,
+    song_config: Option<Res<SongConfig>>,
+    materials: Res<ArrowMaterialResource>,
+    arrows: Query<(Entity, &Transform, &Arrow), Added<Arrow>>,
+) {
+    let song_config = match song_config {
+        Some(song_config) => song_config,
+        None => return,
+    };
+    let mods = song_config.mods;
+    arrows.for_each(|(entity, transform, arrow)| {
+        // 根据速度获取材质，遮挡箭头时使用半透明的材质
+        let material = if mods.visibility != VisibilityMod::Off {
+            materials.faded(arrow.speed, mods.arrow_alpha(0.0))
+        } else {
+            match arrow.speed {
+                Speed::Slow => materials.red_texture.clone(),
+                Speed::Medium => materials.blue_texture.clone(),
+                Speed::Fast => materials.green_texture.clone(),
+            }
+        };
+        cmd.entity(entity)
+            .insert_bundle(arrow_sprite(material, *transform, song_config.versus));
+    });
+}
+
+/// 按箭头走过的距离改变透明度，超过目标后下落的箭头也按距离计算
+fn apply_lane_cover(
+    song_config: Res<SongConfig>,
+    materials: Res<ArrowMaterialResource>,
+    arrows: Query<(&Transform, &Arrow, &mut Handle<ColorMaterial>)>,
+) {
+    let mods = song_config.mods;
+    if mods.visibility == VisibilityMod::Off {
+        return;
+    }
+    arrows.for_each_mut(|(transform, arrow, mut material)| {
+        let progress = (transform.translation.x - SPAWN_POSITION) / DISTANCE;
+        let faded = materials.faded(arrow.speed, mods.arrow_alpha(progress));
+        if *material != faded {
+            *material = faded;
+        }
+    });
+}
+
 /// 目标箭头
 struct TargetArrow;
-/// 初始化目标箭头
-fn setup_target_arrows(mut cmd: Commands, materials: Res<ArrowMaterialResource>) {
-    let directions = Directions::directions();
-    for direction in directions.iter() {
-        let sprite_bundle =
-            spawn_arrow_sprite(materials.border_texture.clone(), direction, TARGET_POSITION);
-        cmd.spawn_bundle(sprite_bundle).insert(TargetArrow);
+/// 初始化目标箭头，双人对战时每个玩家一组
+fn setup_target_arrows(
+    mut cmd: Commands,
+    materials: Res<ArrowMaterialResource>,
+    song_config: Res<SongConfig>,
+) {
+    let players: &[Player] = if song_config.versus {
+        &Player::ALL
+    } else {
+        &[Player::One]
+    };
+    for player in players {
+        for direction in Directions::directions().iter() {
+            let position = Vec2::new(
+                TARGET_POSITION,
+                player.lane_y(*direction, song_config.versus),
+            );
+            let sprite_bundle = arrow_sprite(
+                materials.border_texture.clone(),
+                arrow_transform(direction, position),
+                song_config.versus,
+            );
+            cmd.spawn_bundle(sprite_bundle).insert(TargetArrow);
+        }
     }
 }
 
-/// 辅助函数
-fn spawn_arrow_sprite(
-    material: Handle<ColorMaterial>,
-    direction: &Directions,
-    start_position: f32,
-) -> SpriteBundle {
+/// 辅助函数，箭头的初始位置和方向
+fn arrow_transform(direction: &Directions, position: Vec2) -> Transform {
     // 初始位置
-    let mut transform = Transform::from_translation(Vec3::new(start_position, direction.y(), 1.0));
+    let mut transform = Transform::from_translation(position.extend(1.0));
     // 旋转到正确方向
     transform.rotate(Quat::from_rotation_z(direction.rotation()));
+    transform
+}
+
+/// 辅助函数，双人对战时箭头缩小
+fn arrow_sprite(
+    material: Handle<ColorMaterial>,
+    transform: Transform,
+    versus: bool,
+) -> SpriteBundle {
     // 大小
-    let sprite = Sprite::new(Vec2::new(ARROW_SIZE, ARROW_SIZE));
+    let size = if versus {
+        ARROW_SIZE * VERSUS_LANE_SCALE
+    } else {
+        ARROW_SIZE
+    };
+    let sprite = Sprite::new(Vec2::new(size, size));
     SpriteBundle {
         sprite,
         material,
         transform,
+        global_transform: GlobalTransform::from(transform),
         ..Default::default()
     }
 }
@@ -192,9 +361,26 @@ fn spawn_arrow_sprite(
 /// Event struct
 pub struct CorrectArrowEvent {
     pub direction: Directions,
+    pub player: Player,
+    /// 按正常速度时离目标的距离
+    pub distance: f32,
     pub points: usize,
+    pub judgement: Judgement,
+    /// 击中时间减去箭头到达目标的时间（秒），负数表示提前
+    pub offset: f32,
+    /// 箭头的按键音
+    pub keysound: Option<Handle<AudioSource>>,
 }
 
+/// 箭头没有被击中
+pub struct MissArrowEvent {
+    pub direction: Directions,
+    pub player: Player,
+}
+
+/// 歌曲结束：所有箭头都已判定，或者血条减到 0
+pub struct SongFinishedEvent;
+
 type Arrows = Or<(With<Arrow>, With<TargetArrow>)>;
 fn despawn_all_arrows(mut cmd: Commands, q: Query<Entity, Arrows>) {
     q.for_each(|e| cmd.entity(e).despawn_recursive());
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+use bevy::prelude::AudioSource;
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source, StreamError};
+
+use super::stretch::TimeStretch;
+use super::SoundHandle;
+
+/// 音频输出后端，每个声音由 SoundHandle 区分
+pub trait AudioBackend: Send + Sync {
+    /// 从 position 秒开始以 rate 倍速播放
+    fn play(
+        &mut self,
+        id: SoundHandle,
+        source: &AudioSource,
+        position: f64,
+        volume: f32,
+        rate: f32,
+        paused: bool,
+    );
+    fn stop(&mut self, id: SoundHandle);
+    fn set_paused(&mut self, id: SoundHandle, paused: bool);
+    fn set_volume(&mut self, id: SoundHandle, volume: f32);
+    /// 声音是否已经播放完（或者不存在）
+    fn is_finished(&self, id: SoundHandle) -> bool;
+}
+
+/// 使用 rodio 输出到声卡，每个声音一个 Sink
+pub struct RodioBackend {
+    stream: OutputStreamHandle,
+    sinks: HashMap<SoundHandle, Sink>,
+}
+
+impl RodioBackend {
+    /// 打开默认输出设备，返回的 OutputStream 需要一直保留
+    pub fn try_default() -> Result<(OutputStream, Self), StreamError> {
+        let (stream, handle) = OutputStream::try_default()?;
+        let backend = Self {
+            stream: handle,
+            sinks: HashMap::new(),
+        };
+        Ok((stream, backend))
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    fn play(
+        &mut self,
+        id: SoundHandle,
+        source: &AudioSource,
+        position: f64,
+        volume: f32,
+        rate: f32,
+        paused: bool,
+    ) {
+        let decoder = match Decoder::new(Cursor::new(source.clone())) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                eprintln!("couldn't decode audio: {}", e);
+                return;
+            }
+        };
+        let sink = match Sink::try_new(&self.stream) {
+            Ok(sink) => sink,
+            Err(e) => {
+                eprintln!("couldn't play audio: {}", e);
+                return;
+            }
+        };
+        sink.set_volume(volume);
+        if paused {
+            sink.pause();
+        }
+        let decoder = decoder.skip_duration(Duration::from_secs_f64(position.max(0.0)));
+        if (rate - 1.0).abs() > f32::EPSILON {
+            sink.append(TimeStretch::new(decoder.convert_samples(), rate));
+        } else {
+            sink.append(decoder);
+        }
+        self.sinks.insert(id, sink);
+    }
+    fn stop(&mut self, id: SoundHandle) {
+        if let Some(sink) = self.sinks.remove(&id) {
+            sink.stop();
+        }
+    }
+    fn set_paused(&mut self, id: SoundHandle, paused: bool) {
+        if let Some(sink) = self.sinks.get(&id) {
+            if paused {
+                sink.pause();
+            } else {
+                sink.play();
+            }
+        }
+    }
+    fn set_volume(&mut self, id: SoundHandle, volume: f32) {
+        if let Some(sink) = self.sinks.get(&id) {
+            sink.set_volume(volume);
+        }
+    }
+    fn is_finished(&self, id: SoundHandle) -> bool {
+        self.sinks.get(&id).map_or(true, |sink| sink.empty())
+    }
+}
+
+/// 不输出声音的后端，用于没有声卡的环境；声音按长度和速度推算结束时间
+#[derive(Default)]
+pub struct NullBackend {
+    sounds: HashMap<SoundHandle, NullSound>,
+    /// 已解码过的音频长度（秒），以音频数据的地址区分
+    lengths: HashMap<usize, f64>,
+}
+
+/// 不输出的声音
+struct NullSound {
+    /// 还要播放多久（秒，按实际时间），不包括 resumed 之后的部分
+    remaining: f64,
+    /// 上次开始计时的时刻，暂停时为 None
+    resumed: Option<Instant>,
+}
+
+impl NullSound {
+    fn remaining(&self) -> f64 {
+        self.remaining - self.resumed.map_or(0.0, |t| t.elapsed().as_secs_f64())
+    }
+}
+
+impl NullBackend {
+    /// 音频长度（秒），解码失败时为 0
+    fn length(&mut self, source: &AudioSource) -> f64 {
+        let key = source.bytes.as_ptr() as usize;
+        *self.lengths.entry(key).or_insert_with(|| {
+            let decoder = match Decoder::new(Cursor::new(source.clone())) {
+                Ok(decoder) => decoder,
+                Err(_) => return 0.0,
+            };
+            if let Some(duration) = decoder.total_duration() {
+                return duration.as_secs_f64();
+            }
+            let frame = decoder.channels() as f64 * decoder.sample_rate() as f64;
+            decoder.count() as f64 / frame
+        })
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn play(
+        &mut self,
+        id: SoundHandle,
+        source: &AudioSource,
+        position: f64,
+        _volume: f32,
+        rate: f32,
+        paused: bool,
+    ) {
+        let remaining = (self.length(source) - position.max(0.0)).max(0.0) / rate as f64;
+        let resumed = if paused { None } else { Some(Instant::now()) };
+        self.sounds.insert(id, NullSound { remaining, resumed });
+    }
+    fn stop(&mut self, id: SoundHandle) {
+        self.sounds.remove(&id);
+    }
+    fn set_paused(&mut self, id: SoundHandle, paused: bool) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            if paused {
+                sound.remaining = sound.remaining();
+                sound.resumed = None;
+            } else if sound.resumed.is_none() {
+                sound.resumed = Some(Instant::now());
+            }
+        }
+    }
+    fn set_volume(&mut self, _id: SoundHandle, _volume: f32) {}
+    fn is_finished(&self, id: SoundHandle) -> bool {
+        self.sounds
+            .get(&id)
+            .map_or(true, |sound| sound.remaining() <= 0.0)
+    }
+}
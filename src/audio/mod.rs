@@ -0,0 +1,137 @@
+use bevy::audio::Mp3Loader;
+use bevy::prelude::*;
+use rodio::OutputStream;
+
+pub use backend::{AudioBackend, NullBackend, RodioBackend};
+pub use player::{AudioChannel, AudioPlayer, SoundHandle};
+
+use crate::arrows::{CorrectArrowEvent, MissArrowEvent};
+use crate::time::ControlledTime;
+use crate::types::SongConfig;
+use crate::AppState;
+
+mod backend;
+mod player;
+mod stretch;
+
+/// 进入游戏后开始播放歌曲的时间（秒）
+const SONG_START: f64 = 3.0;
+/// 歌曲和游戏时间相差超过该值（秒）时重新对齐
+const SYNC_TOLERANCE: f64 = 0.1;
+/// 音量统一的目标响度（dBFS）
+const TARGET_LOUDNESS: f64 = -14.0;
+/// 音量统一的最大增益，避免安静的歌曲被放大到失真
+const MAX_NORMALIZATION_GAIN: f32 = 2.0;
+
+/// 替代 bevy 的音频插件：加载音频资源，并提供可控制的 AudioPlayer
+pub struct AudioPlugin;
+
+impl Plugin for AudioPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        let backend: Box<dyn AudioBackend> = match RodioBackend::try_default() {
+            Ok((stream, backend)) => {
+                app.insert_non_send_resource(AudioOutputStream(stream));
+                Box::new(backend)
+            }
+            Err(e) => {
+                eprintln!("no audio output, playing silently: {}", e);
+                Box::new(NullBackend::default())
+            }
+        };
+        app.add_asset::<AudioSource>()
+            .init_asset_loader::<Mp3Loader>()
+            .insert_resource(AudioPlayer::new(backend))
+            .init_resource::<GameMusic>()
+            .add_system_to_stage(CoreStage::PostUpdate, player::update_audio_player.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(play_song.system())
+                    .with_system(sync_song.system())
+                    .with_system(play_hit_sounds.system()),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Game).with_system(stop_song.system()));
+    }
+}
+
+/// 按谱面中的响度统一歌曲音量，没有响度信息时不调整
+pub fn normalization_gain(loudness: Option<f64>) -> f32 {
+    loudness.map_or(1.0, |loudness| {
+        let gain = 10f64.powf((TARGET_LOUDNESS - loudness) / 20.0) as f32;
+        gain.min(MAX_NORMALIZATION_GAIN)
+    })
+}
+
+/// 输出设备，释放后停止所有声音
+struct AudioOutputStream(OutputStream);
+
+/// 游戏中正在播放的歌曲
+#[derive(Default)]
+struct GameMusic(Option<SoundHandle>);
+
+/// 到时间后播放歌曲，从编辑器试玩时从对应位置开始
+fn play_song(
+    mut player: ResMut<AudioPlayer>,
+    mut music: ResMut<GameMusic>,
+    time: Res<ControlledTime>,
+    config: Res<SongConfig>,
+) {
+    let sec = time.seconds_since_startup();
+    if music.0.is_none() && sec > SONG_START {
+        let source = config.song_audio.clone();
+        let handle = player.play_from(source, AudioChannel::Music, sec - SONG_START);
+        player.set_volume(handle, config.volume);
+        player.set_rate(handle, time.rate());
+        music.0 = Some(handle);
+    }
+}
+
+/// 游戏时间跳转后（练习模式循环）让歌曲跟上，跳到歌曲开始之前时停止，到时间后重新播放
+fn sync_song(
+    mut player: ResMut<AudioPlayer>,
+    mut music: ResMut<GameMusic>,
+    time: Res<ControlledTime>,
+) {
+    let handle = match music.0 {
+        Some(handle) => handle,
+        None => return,
+    };
+    let expected = time.seconds_since_startup() - SONG_START;
+    if expected < 0.0 {
+        player.stop(handle);
+        music.0 = None;
+    } else if let Some(position) = player.position(handle) {
+        if (position - expected).abs() > SYNC_TOLERANCE {
+            player.seek(handle, expected);
+        }
+    }
+}
+
+/// 离开游戏时停止歌曲
+fn stop_song(mut player: ResMut<AudioPlayer>, mut music: ResMut<GameMusic>) {
+    if let Some(handle) = music.0.take() {
+        player.stop(handle);
+    }
+}
+
+/// 击中时播放按键音（没有按键音时播放判定对应的打击音效），没击中时播放 miss 音效
+fn play_hit_sounds(
+    mut player: ResMut<AudioPlayer>,
+    config: Res<SongConfig>,
+    mut correct_events: EventReader<CorrectArrowEvent>,
+    mut miss_events: EventReader<MissArrowEvent>,
+) {
+    for event in correct_events.iter() {
+        let sound = event
+            .keysound
+            .clone()
+            .or_else(|| config.hit_sounds.get(Some(event.judgement)));
+        if let Some(sound) = sound {
+            player.play(sound, AudioChannel::Effects);
+        }
+    }
+    for _ in miss_events.iter() {
+        if let Some(sound) = config.hit_sounds.get(None) {
+            player.play(sound, AudioChannel::Effects);
+        }
+    }
+}
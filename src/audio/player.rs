@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::utils::Instant;
+
+use super::AudioBackend;
+
+/// 音频通道，每个通道有独立的音量
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AudioChannel {
+    /// 歌曲
+    Music,
+    /// 打击音效和按键音
+    Effects,
+    /// 菜单中的歌曲预览
+    Preview,
+}
+
+/// 声音句柄，用于停止、暂停、跳转和调整音量
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct SoundHandle(u64);
+
+/// 播放位置：后端无法查询位置，由开始时间推算
+struct PlaybackClock {
+    /// 上次暂停或跳转时的位置（秒）
+    offset: f64,
+    /// 上次开始计时的时刻，暂停时为 None
+    resumed: Option<Instant>,
+    /// 播放速度
+    rate: f64,
+}
+
+impl PlaybackClock {
+    fn start_at(position: f64) -> Self {
+        Self {
+            offset: position,
+            resumed: Some(Instant::now()),
+            rate: 1.0,
+        }
+    }
+    fn position(&self) -> f64 {
+        self.offset
+            + self
+                .resumed
+                .map_or(0.0, |t| t.elapsed().as_secs_f64() * self.rate)
+    }
+    fn pause(&mut self) {
+        self.offset = self.position();
+        self.resumed = None;
+    }
+    fn resume(&mut self) {
+        if self.resumed.is_none() {
+            self.resumed = Some(Instant::now());
+        }
+    }
+    fn seek(&mut self, position: f64) {
+        self.offset = position;
+        if self.resumed.is_some() {
+            self.resumed = Some(Instant::now());
+        }
+    }
+    fn set_rate(&mut self, rate: f64) {
+        self.seek(self.position());
+        self.rate = rate;
+    }
+}
+
+struct Sound {
+    source: Handle<AudioSource>,
+    channel: AudioChannel,
+    volume: f32,
+    clock: PlaybackClock,
+    /// 是否已经交给后端（音频加载完成后才能播放）
+    started: bool,
+}
+
+impl Sound {
+    fn paused(&self) -> bool {
+        self.clock.resumed.is_none()
+    }
+}
+
+/// 可控制的音频播放
+/// 声音在音频加载完成后开始，开始位置会算上等待加载的时间，保持和游戏时间同步
+pub struct AudioPlayer {
+    backend: Box<dyn AudioBackend>,
+    sounds: HashMap<SoundHandle, Sound>,
+    channel_volumes: HashMap<AudioChannel, f32>,
+    next_id: u64,
+}
+
+impl AudioPlayer {
+    pub fn new(backend: Box<dyn AudioBackend>) -> Self {
+        Self {
+            backend,
+            sounds: HashMap::new(),
+            channel_volumes: HashMap::new(),
+            next_id: 0,
+        }
+    }
+    /// 从头播放
+    pub fn play(&mut self, source: Handle<AudioSource>, channel: AudioChannel) -> SoundHandle {
+        self.play_from(source, channel, 0.0)
+    }
+    /// 从 position 秒开始播放
+    pub fn play_from(
+        &mut self,
+        source: Handle<AudioSource>,
+        channel: AudioChannel,
+        position: f64,
+    ) -> SoundHandle {
+        let id = SoundHandle(self.next_id);
+        self.next_id += 1;
+        self.sounds.insert(
+            id,
+            Sound {
+                source,
+                channel,
+                volume: 1.0,
+                clock: PlaybackClock::start_at(position),
+                started: false,
+            },
+        );
+        id
+    }
+    pub fn stop(&mut self, id: SoundHandle) {
+        if self.sounds.remove(&id).is_some() {
+            self.backend.stop(id);
+        }
+    }
+    pub fn pause(&mut self, id: SoundHandle) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.clock.pause();
+            self.backend.set_paused(id, true);
+        }
+    }
+    pub fn resume(&mut self, id: SoundHandle) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.clock.resume();
+            self.backend.set_paused(id, false);
+        }
+    }
+    pub fn is_paused(&self, id: SoundHandle) -> bool {
+        self.sounds.get(&id).map_or(false, |sound| sound.paused())
+    }
+    /// 跳转到 position 秒
+    pub fn seek(&mut self, id: SoundHandle, position: f64) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.clock.seek(position.max(0.0));
+            // 后端不支持跳转，重新开始播放
+            if sound.started {
+                self.backend.stop(id);
+                sound.started = false;
+            }
+        }
+    }
+    /// 改变播放速度（不改变音高），从当前位置继续
+    pub fn set_rate(&mut self, id: SoundHandle, rate: f64) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.clock.set_rate(rate);
+            // 后端不支持中途变速，重新开始播放
+            if sound.started {
+                self.backend.stop(id);
+                sound.started = false;
+            }
+        }
+    }
+    /// 当前播放位置（秒），声音已结束时为 None
+    pub fn position(&self, id: SoundHandle) -> Option<f64> {
+        self.sounds.get(&id).map(|sound| sound.clock.position())
+    }
+    /// 设置单个声音的音量，会再乘上通道音量
+    pub fn set_volume(&mut self, id: SoundHandle, volume: f32) {
+        if let Some(sound) = self.sounds.get_mut(&id) {
+            sound.volume = volume;
+            let channel_volume = self.channel_volumes.get(&sound.channel).unwrap_or(&1.0);
+            self.backend.set_volume(id, volume * channel_volume);
+        }
+    }
+    pub fn set_channel_volume(&mut self, channel: AudioChannel, volume: f32) {
+        self.channel_volumes.insert(channel, volume);
+        for (id, sound) in self.sounds.iter() {
+            if sound.channel == channel {
+                self.backend.set_volume(*id, sound.volume * volume);
+            }
+        }
+    }
+
+    /// 开始已加载的声音，移除播放完的声音
+    fn update(&mut self, sources: &Assets<AudioSource>) {
+        let backend = &mut self.backend;
+        let channel_volumes = &self.channel_volumes;
+        self.sounds.retain(|id, sound| {
+            if sound.started {
+                return !backend.is_finished(*id);
+            }
+            if let Some(source) = sources.get(&sound.source) {
+                let channel_volume = channel_volumes.get(&sound.channel).cloned().unwrap_or(1.0);
+                let volume = sound.volume * channel_volume;
+                let position = sound.clock.position();
+                let rate = sound.clock.rate as f32;
+                backend.play(*id, source, position, volume, rate, sound.paused());
+                sound.started = true;
+            }
+            true
+        });
+    }
+}
+
+pub(super) fn update_audio_player(
+    mut player: ResMut<AudioPlayer>,
+    sources: Res<Assets<AudioSource>>,
+) {
+    player.update(&sources);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+    use crate::audio::NullBackend;
+
+    /// 每个 MP3 帧的采样数和采样率
+    const FRAME_SAMPLES: usize = 1152;
+    const SAMPLE_RATE: usize = 44100;
+    /// 位置误差（秒），测试中的等待不精确
+    const TOLERANCE: f64 = 0.03;
+
+    /// 静音的 MP3：MPEG-1 Layer III，128 kbps，44.1 kHz，边信息全为 0
+    fn silent_mp3(frames: usize) -> AudioSource {
+        let mut frame = vec![0u8; 144 * 128_000 / SAMPLE_RATE];
+        frame[..4].copy_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+        AudioSource {
+            bytes: frame.repeat(frames).into(),
+        }
+    }
+
+    /// 后端收到的播放请求
+    #[derive(Default)]
+    struct BackendLog {
+        /// 每次开始播放的（声音，位置，速度）
+        plays: Vec<(SoundHandle, f64, f32)>,
+        volumes: HashMap<SoundHandle, f32>,
+    }
+
+    /// 记录请求，其他交给 NullBackend
+    struct LoggingBackend {
+        null: NullBackend,
+        log: Arc<Mutex<BackendLog>>,
+    }
+
+    impl AudioBackend for LoggingBackend {
+        fn play(
+            &mut self,
+            id: SoundHandle,
+            source: &AudioSource,
+            position: f64,
+            volume: f32,
+            rate: f32,
+            paused: bool,
+        ) {
+            let mut log = self.log.lock().unwrap();
+            log.plays.push((id, position, rate));
+            log.volumes.insert(id, volume);
+            self.null.play(id, source, position, volume, rate, paused);
+        }
+        fn stop(&mut self, id: SoundHandle) {
+            self.null.stop(id);
+        }
+        fn set_paused(&mut self, id: SoundHandle, paused: bool) {
+            self.null.set_paused(id, paused);
+        }
+        fn set_volume(&mut self, id: SoundHandle, volume: f32) {
+            self.log.lock().unwrap().volumes.insert(id, volume);
+            self.null.set_volume(id, volume);
+        }
+        fn is_finished(&self, id: SoundHandle) -> bool {
+            self.null.is_finished(id)
+        }
+    }
+
+    struct TestPlayer {
+        player: AudioPlayer,
+        log: Arc<Mutex<BackendLog>>,
+        app: App,
+        source: Handle<AudioSource>,
+    }
+
+    impl TestPlayer {
+        /// frames 帧长的静音
+        fn new(frames: usize) -> Self {
+            let mut builder = App::build();
+            builder
+                .add_plugins(MinimalPlugins)
+                .add_plugin(AssetPlugin)
+                .add_asset::<AudioSource>();
+            let mut app = builder.app;
+            let source = app
+                .world
+                .get_resource_mut::<Assets<AudioSource>>()
+                .unwrap()
+                .add(silent_mp3(frames));
+            let log = Arc::new(Mutex::new(BackendLog::default()));
+            let backend = LoggingBackend {
+                null: NullBackend::default(),
+                log: log.clone(),
+            };
+            Self {
+                player: AudioPlayer::new(Box::new(backend)),
+                log,
+                app,
+                source,
+            }
+        }
+        fn play(&mut self) -> SoundHandle {
+            let id = self.player.play(self.source.clone(), AudioChannel::Music);
+            self.update();
+            id
+        }
+        fn update(&mut self) {
+            let sources = self
+                .app
+                .world
+                .get_resource::<Assets<AudioSource>>()
+                .unwrap();
+            self.player.update(sources);
+        }
+        fn plays(&self) -> Vec<(SoundHandle, f64, f32)> {
+            self.log.lock().unwrap().plays.clone()
+        }
+        fn volume(&self, id: SoundHandle) -> Option<f32> {
+            self.log.lock().unwrap().volumes.get(&id).copied()
+        }
+    }
+
+    fn wait(seconds: f64) {
+        sleep(Duration::from_secs_f64(seconds));
+    }
+
+    fn assert_near(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("sound has stopped");
+        assert!(
+            (actual - expected).abs() < TOLERANCE,
+            "position {} vs {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn stop_removes_the_sound() {
+        let mut test = TestPlayer::new(100);
+        let id = test.play();
+        assert!(test.player.position(id).is_some());
+        test.player.stop(id);
+        assert!(test.player.position(id).is_none());
+        test.update();
+        assert!(test.player.position(id).is_none());
+    }
+
+    #[test]
+    fn position_stops_while_paused() {
+        let mut test = TestPlayer::new(100);
+        let id = test.play();
+        wait(0.1);
+        test.player.pause(id);
+        assert!(test.player.is_paused(id));
+        let paused_at = test.player.position(id).unwrap();
+        assert_near(Some(paused_at), 0.1);
+        wait(0.1);
+        test.update();
+        assert_near(test.player.position(id), paused_at);
+        test.player.resume(id);
+        wait(0.1);
+        assert_near(test.player.position(id), paused_at + 0.1);
+    }
+
+    #[test]
+    fn seek_restarts_at_the_new_position() {
+        let mut test = TestPlayer::new(200);
+        let id = test.play();
+        test.player.seek(id, 2.0);
+        assert_near(test.player.position(id), 2.0);
+        test.update();
+        let plays = test.plays();
+        assert_eq!(plays.len(), 2);
+        assert_eq!(plays[1].0, id);
+        assert!((plays[1].1 - 2.0).abs() < TOLERANCE);
+    }
+
+    #[test]
+    fn rate_changes_how_fast_position_moves() {
+        let mut test = TestPlayer::new(200);
+        let id = test.play();
+        test.player.set_rate(id, 2.0);
+        let start = test.player.position(id).unwrap();
+        wait(0.1);
+        assert_near(test.player.position(id), start + 0.2);
+        test.update();
+        let plays = test.plays();
+        assert_eq!(plays.len(), 2);
+        assert!((plays[1].2 - 2.0).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn channel_volume_multiplies_sound_volume() {
+        let mut test = TestPlayer::new(100);
+        test.player.set_channel_volume(AudioChannel::Music, 0.5);
+        let id = test.play();
+        assert_eq!(test.volume(id), Some(0.5));
+        test.player.set_volume(id, 0.5);
+        assert_eq!(test.volume(id), Some(0.25));
+        test.player.set_channel_volume(AudioChannel::Effects, 0.1);
+        assert_eq!(test.volume(id), Some(0.25));
+        test.player.set_channel_volume(AudioChannel::Music, 1.0);
+        assert_eq!(test.volume(id), Some(0.5));
+    }
+
+    #[test]
+    fn finishes_after_length_divided_by_rate() {
+        // 大约 0.52 秒，两倍速时 0.26 秒播放完
+        let frames = 20;
+        let length = (frames * FRAME_SAMPLES) as f64 / SAMPLE_RATE as f64;
+        let mut test = TestPlayer::new(frames);
+        let id = test.player.play(test.source.clone(), AudioChannel::Music);
+        test.player.set_rate(id, 2.0);
+        test.update();
+        wait(length / 2.0 - 0.1);
+        test.update();
+        assert!(test.player.position(id).is_some());
+        wait(0.2);
+        test.update();
+        assert!(test.player.position(id).is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use std::f32::consts::PI;
+use std::time::Duration;
+
+use rodio::Source;
+
+/// 每帧长度（每个声道的采样数）
+const FRAME: usize = 2048;
+/// 输出的帧移，帧长的一半，汉宁窗叠加后幅度不变
+const HOP: usize = FRAME / 2;
+/// 在理想位置前后寻找衔接最好的位置的范围（采样数）
+const TOLERANCE: usize = 256;
+
+/// 变速不变调（WSOLA）：按 rate 倍的步长从输入中取帧，
+/// 在理想位置附近寻找和上一帧衔接最好的位置，再以固定帧移叠加输出
+pub struct TimeStretch<S> {
+    source: S,
+    channels: usize,
+    sample_rate: u32,
+    rate: f64,
+    /// 交错的输入采样，第一个是第 input_start 帧
+    input: Vec<f32>,
+    input_start: usize,
+    source_done: bool,
+    /// 下一帧在输入中的理想位置
+    analysis: f64,
+    /// 上一帧实际取的位置
+    previous: Option<usize>,
+    window: Vec<f32>,
+    /// 上一帧的后半部分，和下一帧的前半部分叠加
+    overlap: Vec<f32>,
+    output: Vec<f32>,
+    output_pos: usize,
+    finished: bool,
+}
+
+impl<S: Source<Item = f32>> TimeStretch<S> {
+    pub fn new(source: S, rate: f32) -> Self {
+        let channels = source.channels().max(1) as usize;
+        let sample_rate = source.sample_rate();
+        let window = (0..FRAME)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FRAME as f32).cos())
+            .collect();
+        Self {
+            source,
+            channels,
+            sample_rate,
+            rate: rate as f64,
+            input: Vec::new(),
+            input_start: 0,
+            source_done: false,
+            analysis: 0.0,
+            previous: None,
+            window,
+            overlap: vec![0.0; HOP * channels],
+            output: Vec::new(),
+            output_pos: 0,
+            finished: false,
+        }
+    }
+    /// 已读入的帧数
+    fn frames_read(&self) -> usize {
+        self.input_start + self.input.len() / self.channels
+    }
+    /// 读入输入直到 end 帧，输入结束时停止
+    fn fill(&mut self, end: usize) {
+        while !self.source_done && self.frames_read() < end {
+            for _ in 0..self.channels {
+                match self.source.next() {
+                    Some(sample) => self.input.push(sample),
+                    None => {
+                        self.source_done = true;
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    /// 输入结束后的位置为 0
+    fn sample(&self, frame: usize, channel: usize) -> f32 {
+        frame
+            .checked_sub(self.input_start)
+            .and_then(|i| self.input.get(i * self.channels + channel))
+            .copied()
+            .unwrap_or(0.0)
+    }
+    fn mono(&self, frame: usize) -> f32 {
+        (0..self.channels).map(|ch| self.sample(frame, ch)).sum()
+    }
+    /// 在 target 附近寻找和 natural 开始的片段最相似的位置
+    fn best_position(&self, target: usize, natural: usize) -> usize {
+        let mut best = (target, f32::MIN);
+        for candidate in (target.saturating_sub(TOLERANCE)..=target + TOLERANCE).step_by(2) {
+            let (mut correlation, mut energy) = (0.0, 0.0);
+            for i in (0..HOP).step_by(4) {
+                let sample = self.mono(candidate + i);
+                correlation += sample * self.mono(natural + i);
+                energy += sample * sample;
+            }
+            let similarity = correlation / (energy.sqrt() + f32::EPSILON);
+            if similarity > best.1 {
+                best = (candidate, similarity);
+            }
+        }
+        best.0
+    }
+    /// 输出一个帧移的采样
+    fn process_hop(&mut self) {
+        let target = self.analysis.round() as usize;
+        let position = match self.previous {
+            Some(previous) => {
+                let natural = previous + HOP;
+                self.fill((target + TOLERANCE).max(natural) + HOP);
+                self.best_position(target, natural)
+            }
+            None => target,
+        };
+        self.fill(position + FRAME);
+        self.output.clear();
+        self.output_pos = 0;
+        if self.source_done && target >= self.frames_read() {
+            // 输入已经用完，输出最后的重叠部分，不输出输入结束之后的静音
+            let tail = match self.previous {
+                Some(previous) => self.frames_read().saturating_sub(previous + HOP),
+                None => 0,
+            };
+            self.overlap.truncate(tail.min(HOP) * self.channels);
+            self.output.append(&mut self.overlap);
+            self.finished = true;
+            return;
+        }
+        let channels = self.channels;
+        for i in 0..HOP {
+            for ch in 0..channels {
+                let sample = self.sample(position + i, ch) * self.window[i];
+                self.output.push(self.overlap[i * channels + ch] + sample);
+            }
+        }
+        for i in 0..HOP {
+            for ch in 0..channels {
+                self.overlap[i * channels + ch] =
+                    self.sample(position + HOP + i, ch) * self.window[HOP + i];
+            }
+        }
+        self.previous = Some(position);
+        self.analysis += HOP as f64 * self.rate;
+        // 丢掉之后不会再用到的输入
+        let keep_from = (position + HOP).min((self.analysis as usize).saturating_sub(TOLERANCE));
+        if keep_from > self.input_start {
+            let drop = ((keep_from - self.input_start) * channels).min(self.input.len());
+            self.input.drain(..drop);
+            self.input_start += drop / channels;
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for TimeStretch<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        while self.output_pos >= self.output.len() {
+            if self.finished {
+                return None;
+            }
+            self.process_hop();
+        }
+        let sample = self.output[self.output_pos];
+        self.output_pos += 1;
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for TimeStretch<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rodio::buffer::SamplesBuffer;
+
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    /// 两个声道的噪声，每个位置都不一样，衔接时不会错位
+    fn noise(frames: usize) -> Vec<f32> {
+        let mut state = 1u32;
+        (0..frames * 2)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    fn stretch(input: &[f32], rate: f32) -> Vec<f32> {
+        let source = SamplesBuffer::new(2, SAMPLE_RATE, input.to_vec());
+        TimeStretch::new(source, rate).collect()
+    }
+
+    #[test]
+    fn output_length_follows_rate() {
+        let frames = SAMPLE_RATE as usize;
+        let input = noise(frames);
+        for &rate in &[0.5, 0.75, 1.0, 1.25, 1.5] {
+            let output = stretch(&input, rate);
+            assert_eq!(output.len() % 2, 0);
+            let expected = frames as f64 / rate as f64;
+            let actual = (output.len() / 2) as f64;
+            assert!(
+                (actual - expected).abs() <= HOP as f64,
+                "rate {}: {} frames, expected {}",
+                rate,
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn rate_one_passes_through() {
+        let frames = SAMPLE_RATE as usize;
+        let input = noise(frames);
+        let output = stretch(&input, 1.0);
+        // 第一个帧移是淡入，之后相邻两帧的窗口加起来为 1
+        for i in HOP * 2..(frames - HOP) * 2 {
+            assert!(
+                (output[i] - input[i]).abs() < 1e-4,
+                "sample {}: {} vs {}",
+                i,
+                output[i],
+                input[i]
+            );
+        }
+    }
+}
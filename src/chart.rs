@@ -0,0 +1,359 @@
+use std::sync::mpsc::{channel, Receiver};
+
+use bevy::asset::{AssetLoader, LoadContext, LoadState, LoadedAsset};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::map_maker::MapMakerSession;
+use crate::practice::{PracticeLoop, PracticeSetup};
+use crate::settings::Settings;
+use crate::types::{chart_hash, SongConfig, SongConfigToml, SongSounds};
+use crate::AppState;
+
+/// 谱面所在的文件夹
+const SONGS_DIR: &str = "assets/songs";
+
+/// 谱面资源：用 AssetServer 加载谱面，文件修改后自动重新加载
+pub struct ChartPlugin;
+
+impl Plugin for ChartPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<SongConfigToml>()
+            .init_asset_loader::<ChartLoader>()
+            .insert_non_send_resource(SongsWatcher::new())
+            .add_event::<SongsChangedEvent>()
+            .add_startup_system(watch_for_changes.system())
+            .add_system(watch_songs_dir.system())
+            .add_system_set(
+                SystemSet::on_update(AppState::Loading).with_system(finish_loading.system()),
+            )
+            .add_system_set(SystemSet::on_exit(AppState::Loading).with_system(end_loading.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(prompt_playing_chart_reload.system())
+                    .with_system(reload_playing_chart.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Game).with_system(despawn_reload_prompt.system()),
+            );
+    }
+}
+
+/// 解析 toml 谱面
+#[derive(Default)]
+struct ChartLoader;
+
+impl AssetLoader for ChartLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut chart: SongConfigToml = toml::from_slice(bytes)?;
+            chart.hash = chart_hash(bytes);
+            load_context.set_default_asset(LoadedAsset::new(chart));
+            Ok(())
+        })
+    }
+    fn extensions(&self) -> &[&str] {
+        &["toml"]
+    }
+}
+
+/// 加载的资源文件修改后重新加载
+fn watch_for_changes(asset_server: Res<AssetServer>) {
+    if let Err(e) = asset_server.watch_for_changes() {
+        eprintln!("couldn't watch assets: {:?}", e);
+    }
+}
+
+/// 歌曲文件夹中有文件新增、修改或删除
+pub struct SongsChangedEvent;
+
+/// 监视歌曲文件夹，包括子文件夹
+struct SongsWatcher {
+    /// 创建失败时为 None，不再监视
+    _watcher: Option<RecommendedWatcher>,
+    events: Receiver<notify::Result<notify::Event>>,
+}
+
+impl SongsWatcher {
+    fn new() -> Self {
+        let (sender, events) = channel();
+        let watcher = RecommendedWatcher::new_immediate(move |event| {
+            // 接收端已经销毁时忽略
+            let _ = sender.send(event);
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(SONGS_DIR, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+        let watcher = watcher
+            .map_err(|e| eprintln!("couldn't watch {}: {}", SONGS_DIR, e))
+            .ok();
+        Self {
+            _watcher: watcher,
+            events,
+        }
+    }
+}
+
+/// 一帧内的多个文件事件合并为一个 SongsChangedEvent
+fn watch_songs_dir(
+    watcher: NonSend<SongsWatcher>,
+    mut changed_event: EventWriter<SongsChangedEvent>,
+) {
+    // 只是读取文件不算变化；any 会提前结束，先取出全部事件
+    let events: Vec<_> = watcher.events.try_iter().collect();
+    let changed = events
+        .iter()
+        .any(|event| matches!(event, Ok(event) if !matches!(event.kind, EventKind::Access(_))));
+    if changed {
+        changed_event.send(SongsChangedEvent);
+    }
+}
+
+/// 加载谱面后要做的事
+pub enum ChartPurpose {
+    Play,
+    /// 在谱面上叠加录制
+    Overdub,
+    /// 练习谱面的一段
+    Practice,
+    /// 双人对战，2P 使用另一个谱面（可以是同一个）
+    Versus {
+        chart_file: String,
+        handle: Handle<SongConfigToml>,
+    },
+}
+
+impl ChartPurpose {
+    /// 双人对战，同时开始加载 2P 的谱面
+    pub fn versus(chart_file: String, asset_server: &AssetServer) -> Self {
+        let handle = asset_server.load(&*format!("songs/{}", chart_file));
+        ChartPurpose::Versus { chart_file, handle }
+    }
+}
+
+/// 正在加载的谱面
+pub struct LoadingChart {
+    /// 谱面文件（相对于 assets/songs）
+    chart_file: String,
+    handle: Handle<SongConfigToml>,
+    purpose: ChartPurpose,
+    /// 谱面加载完成后开始加载的音效，加载完才离开 Loading
+    sounds: Option<Vec<SongSounds>>,
+}
+
+/// 开始加载谱面，加载完成后开始游戏或者进入编辑器
+pub(crate) fn load_chart(
+    cmd: &mut Commands,
+    state: &mut State<AppState>,
+    asset_server: &AssetServer,
+    chart_file: String,
+    purpose: ChartPurpose,
+) {
+    let handle = asset_server.load(&*format!("songs/{}", chart_file));
+    cmd.insert_resource(LoadingChart {
+        chart_file,
+        handle,
+        purpose,
+        sounds: None,
+    });
+    state.set(AppState::Loading).unwrap();
+}
+
+fn finish_loading(
+    mut cmd: Commands,
+    mut state: ResMut<State<AppState>>,
+    mut loading: ResMut<LoadingChart>,
+    charts: Res<Assets<SongConfigToml>>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+) {
+    // 双人对战时要等两个谱面都加载完
+    let mut handles = vec![(&loading.chart_file, &loading.handle)];
+    if let ChartPurpose::Versus { chart_file, handle } = &loading.purpose {
+        handles.push((chart_file, handle));
+    }
+    let mut charts_loaded = Vec::new();
+    for (chart_file, handle) in handles {
+        match asset_server.get_load_state(handle) {
+            LoadState::Loaded => {}
+            LoadState::Failed => {
+                eprintln!("couldn't load {}", chart_file);
+                state.set(AppState::Menu).unwrap();
+                return;
+            }
+            _ => return,
+        }
+        match charts.get(handle) {
+            Some(chart) => charts_loaded.push((chart_file.clone(), chart)),
+            None => return,
+        }
+    }
+    // 谱面加载完后再加载谱面声明的音效，音效加载完才开始，避免开头的按键音没有声音
+    let sounds = loading.sounds.get_or_insert_with(|| {
+        charts_loaded
+            .iter()
+            .map(|(chart_file, chart)| {
+                SongSounds::load(
+                    &chart.hit_sounds,
+                    &chart.keysounds,
+                    chart_file,
+                    &asset_server,
+                )
+            })
+            .collect()
+    });
+    let sound_handles = sounds.iter().flat_map(SongSounds::handle_ids);
+    match asset_server.get_group_load_state(sound_handles) {
+        LoadState::Loaded => {}
+        // 缺少音效时仍然可以游戏
+        LoadState::Failed => eprintln!("couldn't load some sounds of {}", loading.chart_file),
+        _ => return,
+    }
+    let chart = match charts.get(&loading.handle) {
+        Some(chart) => chart,
+        None => return,
+    };
+    let chart_file = loading.chart_file.clone();
+    let build_config = |chart, chart_file, handle: &Handle<SongConfigToml>| {
+        SongConfig::from_chart(
+            chart,
+            chart_file,
+            handle.clone(),
+            &asset_server,
+            settings.gameplay.mods,
+        )
+    };
+    match &loading.purpose {
+        ChartPurpose::Play => {
+            let config = build_config(chart, chart_file, &loading.handle);
+            cmd.insert_resource(config);
+            state.set(AppState::Game).unwrap();
+        }
+        ChartPurpose::Overdub => {
+            cmd.insert_resource(MapMakerSession::Overdub {
+                chart_file,
+                chart: loading.handle.clone(),
+            });
+            state.set(AppState::MakeMap).unwrap();
+        }
+        ChartPurpose::Practice => {
+            let config = build_config(chart, chart_file, &loading.handle);
+            cmd.insert_resource(PracticeSetup::new(chart, config));
+            state.set(AppState::Practice).unwrap();
+        }
+        ChartPurpose::Versus {
+            chart_file: rival_file,
+            handle: rival_handle,
+        } => {
+            let rival_chart = match charts.get(rival_handle) {
+                Some(chart) => chart,
+                None => return,
+            };
+            let mut config = build_config(chart, chart_file, &loading.handle);
+            config.add_rival(build_config(rival_chart, rival_file.clone(), rival_handle));
+            cmd.insert_resource(config);
+            state.set(AppState::Game).unwrap();
+        }
+    }
+}
+
+fn end_loading(mut cmd: Commands) {
+    cmd.remove_resource::<LoadingChart>();
+}
+
+/// 谱面在磁盘上被修改后的提示
+pub struct ReloadPrompt;
+
+/// 事件中是否有 chart 被修改（重新加载完成）
+pub fn chart_modified(
+    events: &mut EventReader<AssetEvent<SongConfigToml>>,
+    chart: Option<&Handle<SongConfigToml>>,
+) -> bool {
+    // 读完所有事件，下一帧不再重复处理
+    let events: Vec<_> = events.iter().collect();
+    events
+        .iter()
+        .any(|event| matches!(event, AssetEvent::Modified { handle } if Some(handle) == chart))
+}
+
+/// 显示重新加载的提示
+pub fn spawn_reload_prompt(cmd: &mut Commands, asset_server: &AssetServer, message: &str) {
+    cmd.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            message,
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                font_size: 20.0,
+                color: Color::rgb(0.95, 0.8, 0.3),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    })
+    .insert(ReloadPrompt);
+}
+
+pub fn despawn_reload_prompt(mut cmd: Commands, q: Query<Entity, With<ReloadPrompt>>) {
+    q.for_each(|e| cmd.entity(e).despawn_recursive());
+}
+
+/// 游戏中的谱面被修改时提示
+fn prompt_playing_chart_reload(
+    mut cmd: Commands,
+    mut events: EventReader<AssetEvent<SongConfigToml>>,
+    config: Res<SongConfig>,
+    practice: Option<Res<PracticeLoop>>,
+    prompt: Query<(), With<ReloadPrompt>>,
+    asset_server: Res<AssetServer>,
+) {
+    // 练习时游戏中的配置没有谱面，谱面记在练习里
+    let chart = match &practice {
+        Some(practice) => practice.chart.as_ref(),
+        None => config.chart.as_ref(),
+    };
+    let prompted = prompt.iter().next().is_some();
+    if !prompted && chart_modified(&mut events, chart) {
+        spawn_reload_prompt(
+            &mut cmd,
+            &asset_server,
+            "Chart changed on disk. Press F9 to restart with it.",
+        );
+    }
+}
+
+/// 提示后按 F9 重新开始；练习时回到练习设置，不记录成绩
+fn reload_playing_chart(
+    mut cmd: Commands,
+    mut state: ResMut<State<AppState>>,
+    key_input: Res<Input<KeyCode>>,
+    config: Res<SongConfig>,
+    practice: Option<Res<PracticeLoop>>,
+    prompt: Query<(), With<ReloadPrompt>>,
+    asset_server: Res<AssetServer>,
+) {
+    let prompted = prompt.iter().next().is_some();
+    if prompted && key_input.just_pressed(KeyCode::F9) {
+        let (chart_file, purpose) = match &practice {
+            Some(practice) => (&practice.chart_file, ChartPurpose::Practice),
+            None => (&config.chart_file, ChartPurpose::Play),
+        };
+        if let Some(chart_file) = chart_file.clone() {
+            load_chart(&mut cmd, &mut state, &asset_server, chart_file, purpose);
+        }
+    }
+}
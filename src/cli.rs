@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use toml_edit::{value, Document};
+
+use crate::analysis::{analyze_beat_grid, generate_chart, loudness, DecodedAudio, GenerateOptions};
+use crate::simulation::{simulate, InputScript, DEFAULT_FRAME_RATE};
+use crate::types::{SongConfig, SongConfigToml, SongSounds};
+
+const GENERATE_USAGE: &str =
+    "usage: rhythm generate <audio> [--difficulty 1-10] [--no-snap] [--output <toml>]";
+const BPM_USAGE: &str = "usage: rhythm bpm <audio> [--chart <toml>]";
+const LOUDNESS_USAGE: &str = "usage: rhythm loudness <audio> [--chart <toml>]";
+const SIMULATE_USAGE: &str =
+    "usage: rhythm simulate <chart> [--inputs <toml>] [--offset <ms>] [--fps <n>]";
+
+/// 处理命令行子命令，返回 true 表示已处理，不再启动游戏
+pub fn run(args: &[String]) -> bool {
+    let result = match args.first().map(String::as_str) {
+        Some("generate") => generate(&args[1..]),
+        Some("bpm") => bpm(&args[1..]),
+        Some("loudness") => measure_loudness(&args[1..]),
+        Some("simulate") => run_simulation(&args[1..]),
+        _ => return false,
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+    true
+}
+
+/// 由音频自动生成谱面草稿
+fn generate(args: &[String]) -> Result<(), String> {
+    let mut audio_path = None;
+    let mut output = None;
+    let mut options = GenerateOptions::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--difficulty" => {
+                options.difficulty = iter
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .filter(|d| (1..=10).contains(d))
+                    .ok_or(GENERATE_USAGE)?;
+            }
+            "--no-snap" => options.snap_to_tempo = false,
+            "--output" => output = Some(iter.next().ok_or(GENERATE_USAGE)?.clone()),
+            path => audio_path = Some(path.to_string()),
+        }
+    }
+    let audio_path = audio_path.ok_or(GENERATE_USAGE)?;
+    let path = Path::new(&audio_path);
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| format!("{} is not an audio file", audio_path))?;
+    let audio = DecodedAudio::load(path)
+        .map_err(|e| format!("couldn't decode {}: {}", path.display(), e))?;
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or(GENERATE_USAGE)?;
+    // 默认保存在 assets/songs 下音频所在的文件夹
+    let output = output.unwrap_or_else(|| match path.strip_prefix("assets/songs") {
+        Ok(_) => path.with_extension("toml").to_string_lossy().to_string(),
+        Err(_) => format!("assets/songs/{}.toml", stem),
+    });
+    // 谱面中的音频路径相对于谱面所在的文件夹
+    let chart_dir = Path::new(&output).parent().unwrap_or_else(|| Path::new(""));
+    let filename = path
+        .strip_prefix(chart_dir)
+        .unwrap_or_else(|_| Path::new(file_name))
+        .to_string_lossy()
+        .to_string();
+    if Path::new(&output).exists() {
+        return Err(format!("{} already exists", output));
+    }
+    let chart = generate_chart(format!("{} (draft)", stem), filename, &audio, &options);
+    let toml_text = toml::to_string_pretty(&chart).expect("couldn't convert to toml text");
+    std::fs::write(&output, toml_text).map_err(|e| format!("couldn't write {}: {}", output, e))?;
+    println!("generated {} arrows: {}", chart.arrows.len(), output);
+    Ok(())
+}
+
+/// 检测 BPM 和第一拍位置，可写入谱面
+fn bpm(args: &[String]) -> Result<(), String> {
+    let mut audio_path = None;
+    let mut chart_path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--chart" => chart_path = Some(iter.next().ok_or(BPM_USAGE)?.clone()),
+            path => audio_path = Some(path.to_string()),
+        }
+    }
+    let audio_path = audio_path.ok_or(BPM_USAGE)?;
+    let audio = DecodedAudio::load(Path::new(&audio_path))
+        .map_err(|e| format!("couldn't decode {}: {}", audio_path, e))?;
+    let grid = analyze_beat_grid(&audio).ok_or("no steady beat found")?;
+    println!(
+        "BPM: {:.2}. Offset: {:.3}s. Confidence: {:.0}%.",
+        grid.bpm,
+        grid.offset,
+        grid.confidence * 100.0
+    );
+    if let Some(chart_path) = chart_path {
+        update_chart(&chart_path, |chart| {
+            chart["bpm"] = value(grid.bpm);
+            chart["offset"] = value(grid.offset);
+        })?;
+    }
+    Ok(())
+}
+
+/// 测量歌曲响度，可写入谱面用于音量统一
+fn measure_loudness(args: &[String]) -> Result<(), String> {
+    let mut audio_path = None;
+    let mut chart_path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--chart" => chart_path = Some(iter.next().ok_or(LOUDNESS_USAGE)?.clone()),
+            path => audio_path = Some(path.to_string()),
+        }
+    }
+    let audio_path = audio_path.ok_or(LOUDNESS_USAGE)?;
+    let audio = DecodedAudio::load(Path::new(&audio_path))
+        .map_err(|e| format!("couldn't decode {}: {}", audio_path, e))?;
+    let loudness = loudness(&audio).ok_or("the audio is silent")?;
+    println!("Loudness: {:.1} dB.", loudness);
+    if let Some(chart_path) = chart_path {
+        update_chart(&chart_path, |chart| chart["loudness"] = value(loudness))?;
+    }
+    Ok(())
+}
+
+/// 不打开窗口运行谱面，按键来自脚本，没有脚本时在每个箭头的点击时间加 offset 按下
+fn run_simulation(args: &[String]) -> Result<(), String> {
+    let mut chart_path = None;
+    let mut inputs_path = None;
+    let mut offset_ms = 0.0;
+    let mut frame_rate = DEFAULT_FRAME_RATE;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--inputs" => inputs_path = Some(iter.next().ok_or(SIMULATE_USAGE)?.clone()),
+            "--offset" => {
+                offset_ms = iter
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .ok_or(SIMULATE_USAGE)?;
+            }
+            "--fps" => {
+                frame_rate = iter
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .filter(|fps: &f64| *fps > 0.0)
+                    .ok_or(SIMULATE_USAGE)?;
+            }
+            path => chart_path = Some(path.to_string()),
+        }
+    }
+    let chart_path = chart_path.ok_or(SIMULATE_USAGE)?;
+    let contents = std::fs::read_to_string(&chart_path)
+        .map_err(|e| format!("couldn't read {}: {}", chart_path, e))?;
+    let chart = SongConfigToml::parse(&chart_path, &contents)?;
+    // 不加载音频，按键音和打击音效都为空
+    let config = SongConfig::new(
+        chart.name.clone(),
+        Default::default(),
+        &chart.arrows,
+        SongSounds::default(),
+    );
+    let script = match inputs_path {
+        Some(path) => {
+            let contents = std::fs::read_to_string(&path)
+                .map_err(|e| format!("couldn't read {}: {}", path, e))?;
+            InputScript::parse(&path, &contents)?
+        }
+        None => InputScript::autoplay(&config, offset_ms / 1000.0),
+    };
+    let result = simulate(config, &script, frame_rate);
+    for record in &result.judgements {
+        let judgement = record
+            .judgement
+            .map_or(String::from("Miss"), |judgement| format!("{:?}", judgement));
+        let offset = record.offset.map_or(String::new(), |offset| {
+            format!("{:+.0} ms", offset * 1000.0)
+        });
+        println!(
+            "{:8.3}  {}  {:5}  {:7}  {:3} pts  {}",
+            record.time,
+            record.player.name(),
+            format!("{:?}", record.direction),
+            judgement,
+            record.points,
+            offset
+        );
+    }
+    println!("{}", result.score);
+    Ok(())
+}
+
+/// 读取谱面，只修改给定的键后写回，保留注释和其他字段的顺序
+fn update_chart(chart_path: &str, update: impl FnOnce(&mut Document)) -> Result<(), String> {
+    let contents = std::fs::read_to_string(chart_path)
+        .map_err(|e| format!("couldn't read {}: {}", chart_path, e))?;
+    // 确认是谱面文件
+    SongConfigToml::parse(chart_path, &contents)?;
+    let mut chart: Document = contents
+        .parse()
+        .map_err(|e| format!("couldn't parse {}: {}", chart_path, e))?;
+    update(&mut chart);
+    let toml_text = chart.to_string();
+    std::fs::write(chart_path, toml_text)
+        .map_err(|e| format!("couldn't write {}: {}", chart_path, e))?;
+    println!("updated {}", chart_path);
+    Ok(())
+}
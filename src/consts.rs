@@ -9,6 +9,9 @@ pub const BASE_SPEED: f32 = 200.0;
 /// 箭头大小
 pub const ARROW_SIZE: f32 = 140.0;
 
+/// 双人对战时每个玩家的跑道和箭头缩小的比例
+pub const VERSUS_LANE_SCALE: f32 = 0.5;
+
 /// 箭头生成时的 初始横坐标
 pub const SPAWN_POSITION: f32 = -400.0;
 
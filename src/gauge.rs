@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arrows::{CorrectArrowEvent, MissArrowEvent, SongFinishedEvent};
+use crate::practice::PracticeLoop;
+use crate::score::{Judgement, ScoreResource};
+use crate::settings::Settings;
+use crate::types::SongConfig;
+use crate::AppState;
+
+/// 血条：判定好时回复，错过时减少，减到 0 时失败
+pub struct GaugePlugin;
+
+impl Plugin for GaugePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Gauge>()
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(reset_gauge.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Game).with_system(update_gauge.system()),
+            );
+    }
+}
+
+/// 血条类型
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GaugeType {
+    Normal,
+    /// 不会回复，错过时减少更多
+    Hard,
+    /// 错过一个就失败
+    SuddenDeath,
+    /// 血条减到 0 也不会失败
+    NoFail,
+}
+
+impl Default for GaugeType {
+    fn default() -> Self {
+        GaugeType::Normal
+    }
+}
+
+impl GaugeType {
+    pub const ALL: [GaugeType; 4] = [
+        GaugeType::Normal,
+        GaugeType::Hard,
+        GaugeType::SuddenDeath,
+        GaugeType::NoFail,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            GaugeType::Normal => "Normal",
+            GaugeType::Hard => "Hard",
+            GaugeType::SuddenDeath => "Sudden death",
+            GaugeType::NoFail => "No fail",
+        }
+    }
+    /// 判定后的回复量，judgement 为 None 表示错过（负数）
+    fn change(&self, judgement: Option<Judgement>) -> f32 {
+        match (self, judgement) {
+            (GaugeType::Hard, Some(_)) | (GaugeType::SuddenDeath, Some(_)) => 0.0,
+            (_, Some(Judgement::Perfect)) => 0.02,
+            (_, Some(Judgement::Great)) => 0.015,
+            (_, Some(Judgement::Good)) => 0.005,
+            (GaugeType::Hard, None) => -0.1,
+            (GaugeType::SuddenDeath, None) => -1.0,
+            (_, None) => -0.05,
+        }
+    }
+}
+
+/// 当前的血条
+pub struct Gauge {
+    pub kind: GaugeType,
+    /// 0 到 1
+    pub value: f32,
+}
+
+impl Default for Gauge {
+    fn default() -> Self {
+        Self {
+            kind: GaugeType::Normal,
+            value: 1.0,
+        }
+    }
+}
+
+impl Gauge {
+    fn new(kind: GaugeType) -> Self {
+        Self { kind, value: 1.0 }
+    }
+    /// 血条是否已经减到 0 并导致失败
+    fn is_empty(&self) -> bool {
+        self.kind != GaugeType::NoFail && self.value <= 0.0
+    }
+}
+
+/// 每首歌开始时按设置的类型加满血条
+fn reset_gauge(
+    mut gauge: ResMut<Gauge>,
+    settings: Res<Settings>,
+    practice: Option<Res<PracticeLoop>>,
+    config: Res<SongConfig>,
+) {
+    // 练习和双人对战时不会失败
+    let kind = if practice.is_some() || config.versus {
+        GaugeType::NoFail
+    } else {
+        settings.gameplay.gauge
+    };
+    *gauge = Gauge::new(kind);
+}
+
+/// 根据判定调整血条，减到 0 时歌曲以失败结束
+fn update_gauge(
+    mut gauge: ResMut<Gauge>,
+    mut correct_event: EventReader<CorrectArrowEvent>,
+    mut miss_event: EventReader<MissArrowEvent>,
+    mut score: ResMut<ScoreResource>,
+    mut song_config: ResMut<SongConfig>,
+    mut finished_event: EventWriter<SongFinishedEvent>,
+) {
+    let changes: Vec<f32> = correct_event
+        .iter()
+        .map(|event| Some(event.judgement))
+        .chain(miss_event.iter().map(|_| None))
+        .map(|judgement| gauge.kind.change(judgement))
+        .collect();
+    if song_config.finished {
+        return;
+    }
+    for change in changes {
+        gauge.value = (gauge.value + change).max(0.0).min(1.0);
+    }
+    if gauge.is_empty() {
+        score.fail();
+        song_config.finished = true;
+        finished_event.send(SongFinishedEvent);
+    }
+}
@@ -0,0 +1,243 @@
+use bevy::prelude::*;
+
+use crate::arrows::CorrectArrowEvent;
+use crate::consts::{BASE_SPEED, TARGET_POSITION, THRESHOLD, WINDOW_HEIGHT};
+use crate::score::{Judgement, ScoreResource};
+use crate::time::ControlledTime;
+use crate::types::SongConfig;
+use crate::AppState;
+
+/// 误差条两端对应的误差（毫秒），最慢的箭头的判定范围
+pub const MAX_OFFSET_MS: f32 = THRESHOLD / BASE_SPEED * 1000.0;
+/// 每毫秒在误差条上的长度
+const PIXELS_PER_MS: f32 = 1.0;
+/// 误差条的纵坐标，在最下面的箭头下方
+const BAR_Y: f32 = -WINDOW_HEIGHT / 2.0 + 50.0;
+/// 误差标记显示多久（秒）
+const TICK_LIFETIME: f64 = 3.0;
+/// 提前/延后文字显示多久（秒）
+const INDICATOR_LIFETIME: f64 = 0.6;
+
+/// 击中箭头时显示提前还是延后，以及最近几次击中的误差
+pub struct HitErrorPlugin;
+
+impl Plugin for HitErrorPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(AppState::Game).with_system(setup_hit_error_bar.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Game)
+                .with_system(record_hit_errors.system())
+                .with_system(fade_hit_ticks.system())
+                .with_system(fade_indicator.system())
+                .with_system(update_mean_marker.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Game).with_system(despawn_hit_error_bar.system()),
+        );
+    }
+}
+
+struct HitErrorUI;
+/// 一次击中的误差标记，逐渐消失
+struct HitTick {
+    spawned: f64,
+}
+/// 平均误差的标记
+struct MeanMarker;
+/// 平均误差的文字
+struct MeanText;
+/// 提前/延后文字
+struct EarlyLateText {
+    shown: f64,
+}
+
+/// 误差在误差条上的横坐标，超出范围的停在两端
+fn bar_x(offset_ms: f32) -> f32 {
+    TARGET_POSITION + offset_ms.max(-MAX_OFFSET_MS).min(MAX_OFFSET_MS) * PIXELS_PER_MS
+}
+
+/// 双人对战时跑道占满窗口，不显示误差条
+fn setup_hit_error_bar(
+    mut cmd: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<SongConfig>,
+) {
+    if config.versus {
+        return;
+    }
+    let width = MAX_OFFSET_MS * 2.0 * PIXELS_PER_MS;
+    let mut spawn_rect = |size: Vec2, position: Vec3, color: Color| {
+        cmd.spawn_bundle(SpriteBundle {
+            sprite: Sprite::new(size),
+            material: materials.add(color.into()),
+            transform: Transform::from_translation(position),
+            ..Default::default()
+        })
+        .insert(HitErrorUI)
+        .id()
+    };
+    // 误差条和中间的零点
+    spawn_rect(
+        Vec2::new(width, 4.0),
+        Vec3::new(TARGET_POSITION, BAR_Y, 2.0),
+        Color::rgba(0.5, 0.5, 0.5, 0.6),
+    );
+    spawn_rect(
+        Vec2::new(2.0, 16.0),
+        Vec3::new(TARGET_POSITION, BAR_Y, 2.1),
+        Color::rgb(0.9, 0.9, 0.9),
+    );
+    let marker = spawn_rect(
+        Vec2::new(6.0, 6.0),
+        Vec3::new(TARGET_POSITION, BAR_Y + 14.0, 2.2),
+        Color::NONE,
+    );
+    cmd.entity(marker).insert(MeanMarker);
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Center,
+    };
+    cmd.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: 16.0,
+                color: Color::rgb(0.8, 0.8, 0.8),
+            },
+            alignment,
+        ),
+        transform: Transform::from_translation(Vec3::new(
+            TARGET_POSITION - width / 2.0 - 50.0,
+            BAR_Y,
+            2.0,
+        )),
+        ..Default::default()
+    })
+    .insert(HitErrorUI)
+    .insert(MeanText);
+    cmd.spawn_bundle(Text2dBundle {
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 24.0,
+                color: Color::NONE,
+            },
+            alignment,
+        ),
+        transform: Transform::from_translation(Vec3::new(TARGET_POSITION, BAR_Y + 36.0, 2.0)),
+        ..Default::default()
+    })
+    .insert(HitErrorUI)
+    .insert(EarlyLateText { shown: f64::MIN });
+}
+
+/// 判定颜色
+fn judgement_color(judgement: Judgement) -> Color {
+    match judgement {
+        Judgement::Perfect => Color::rgb(0.4, 0.8, 1.0),
+        Judgement::Great => Color::rgb(0.4, 0.9, 0.4),
+        Judgement::Good => Color::rgb(1.0, 0.7, 0.3),
+    }
+}
+
+/// 击中时在误差条上加一个标记，显示提前还是延后
+fn record_hit_errors(
+    mut cmd: Commands,
+    mut correct_event: EventReader<CorrectArrowEvent>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    time: Res<ControlledTime>,
+    texts: Query<(&mut Text, &mut EarlyLateText)>,
+    config: Res<SongConfig>,
+) {
+    if config.versus {
+        return;
+    }
+    let now = time.seconds_since_startup();
+    for event in correct_event.iter() {
+        let offset_ms = event.offset * 1000.0;
+        cmd.spawn_bundle(SpriteBundle {
+            sprite: Sprite::new(Vec2::new(2.0, 12.0)),
+            material: materials.add(judgement_color(event.judgement).into()),
+            transform: Transform::from_translation(Vec3::new(bar_x(offset_ms), BAR_Y, 2.3)),
+            ..Default::default()
+        })
+        .insert(HitErrorUI)
+        .insert(HitTick { spawned: now });
+        texts.for_each_mut(|(mut text, mut indicator)| {
+            let section = &mut text.sections[0];
+            let (label, color) = if offset_ms < 0.0 {
+                ("EARLY", Color::rgb(0.4, 0.7, 1.0))
+            } else {
+                ("LATE", Color::rgb(1.0, 0.55, 0.3))
+            };
+            section.value = format!("{} {:.0}ms", label, offset_ms.abs());
+            section.style.color = color;
+            indicator.shown = now;
+        });
+    }
+}
+
+/// 误差标记逐渐变透明，到时间后删除
+fn fade_hit_ticks(
+    mut cmd: Commands,
+    time: Res<ControlledTime>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    ticks: Query<(Entity, &HitTick, &Handle<ColorMaterial>)>,
+) {
+    let now = time.seconds_since_startup();
+    ticks.for_each(|(entity, tick, material)| {
+        let age = now - tick.spawned;
+        // 练习循环跳回开始时，之前的标记也删除
+        if !(0.0..TICK_LIFETIME).contains(&age) {
+            cmd.entity(entity).despawn();
+        } else if let Some(material) = materials.get_mut(material) {
+            material.color.set_a((1.0 - age / TICK_LIFETIME) as f32);
+        }
+    });
+}
+
+/// 提前/延后文字显示一会儿后消失
+fn fade_indicator(time: Res<ControlledTime>, texts: Query<(&mut Text, &EarlyLateText)>) {
+    let now = time.seconds_since_startup();
+    texts.for_each_mut(|(mut text, indicator)| {
+        let age = now - indicator.shown;
+        let alpha = (1.0 - age / INDICATOR_LIFETIME).max(0.0) as f32;
+        // 已经消失后不再修改，避免每帧重新排版文字
+        if text.sections[0].style.color.a() > 0.0 {
+            text.sections[0].style.color.set_a(alpha);
+        }
+    });
+}
+
+/// 平均误差标记移动到平均误差的位置，并显示平均误差
+fn update_mean_marker(
+    score: Res<ScoreResource>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    markers: Query<(&mut Transform, &Handle<ColorMaterial>), With<MeanMarker>>,
+    texts: Query<&mut Text, With<MeanText>>,
+) {
+    if !score.is_changed() {
+        return;
+    }
+    if let Some(mean_ms) = score.mean_offset_ms() {
+        texts.for_each_mut(|mut text| {
+            text.sections[0].value = format!("avg {:+.1}ms", mean_ms);
+        });
+        markers.for_each_mut(|(mut transform, material)| {
+            transform.translation.x = bar_x(mean_ms);
+            if let Some(material) = materials.get_mut(material) {
+                material.color = Color::WHITE;
+            }
+        });
+    }
+}
+
+fn despawn_hit_error_bar(mut cmd: Commands, q: Query<Entity, With<HitErrorUI>>) {
+    q.for_each(|e| cmd.entity(e).despawn_recursive());
+}
@@ -1,29 +1,54 @@
 #![warn(clippy::all)]
 
-use bevy::{input::system::exit_on_esc_system, prelude::*};
+use bevy::{app::PluginGroupBuilder, input::system::exit_on_esc_system, prelude::*};
 
-use arrows::ArrowsPlugin;
+use arrows::{ArrowSpritesPlugin, ArrowsPlugin};
 use audio::AudioPlugin;
+use chart::ChartPlugin;
 use consts::{WINDOW_HEIGHT, WINDOW_WIDTH};
-use map_maker::MapMakerPlugin;
+use gauge::GaugePlugin;
+use hit_error::HitErrorPlugin;
+use map_maker::{MapMakerPlugin, Playtest};
 use menu::MenuPlugin;
-use score::ScoreResource;
+use practice::{PracticeLoop, PracticePlugin};
+use records::RecordsPlugin;
+use results::ResultsPlugin;
+use score::ScorePlugin;
+use settings::SettingsPlugin;
 use shaders::ShadersPlugin;
 use time::TimePlugin;
 use ui::UIPlugin;
+use versus::VersusPlugin;
 
+mod analysis;
 mod arrows;
 mod audio;
+mod chart;
+mod cli;
 mod consts;
+mod gauge;
+mod hit_error;
 mod map_maker;
 mod menu;
+mod mods;
+mod practice;
+mod records;
+mod results;
 mod score;
+mod settings;
 mod shaders;
+mod simulation;
 mod time;
 mod types;
 mod ui;
+mod versus;
 
 fn main() {
+    // 命令行子命令（离线工具）
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::run(&args) {
+        return;
+    }
     App::build()
         .insert_resource(Msaa { samples: 4 })
         .insert_resource(WindowDescriptor {
@@ -32,22 +57,41 @@ fn main() {
             height: WINDOW_HEIGHT,
             ..Default::default()
         })
-        .add_plugins(DefaultPlugins)
-        .init_resource::<ScoreResource>()
+        // 使用自己的 AudioPlugin 播放声音
+        .add_plugins_with(DefaultPlugins, |group| {
+            group.disable::<bevy::audio::AudioPlugin>()
+        })
         .add_state(AppState::Menu)
         .add_startup_system(setup.system())
         .add_system(exit_on_esc_system.system())
         .add_system(back_menu.system())
-        .add_plugin(ArrowsPlugin)
+        .add_plugins(GameLogicPlugins)
+        .add_plugin(ArrowSpritesPlugin)
         .add_plugin(UIPlugin)
         .add_plugin(AudioPlugin)
+        .add_plugin(ChartPlugin)
         .add_plugin(ShadersPlugin)
         .add_plugin(MenuPlugin)
-        .add_plugin(TimePlugin)
         .add_plugin(MapMakerPlugin)
+        .add_plugin(SettingsPlugin)
+        .add_plugin(RecordsPlugin)
+        .add_plugin(GaugePlugin)
+        .add_plugin(HitErrorPlugin)
+        .add_plugin(ResultsPlugin)
+        .add_plugin(PracticePlugin)
+        .add_plugin(VersusPlugin)
         .run();
 }
 
+/// 游戏逻辑：时间、箭头的生成和判定、计分，不需要窗口、GPU 和音频设备
+pub struct GameLogicPlugins;
+
+impl PluginGroup for GameLogicPlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group.add(TimePlugin).add(ArrowsPlugin).add(ScorePlugin);
+    }
+}
+
 fn setup(mut cmd: Commands) {
     // 2d 正交相机
     cmd.spawn_bundle(OrthographicCameraBundle::new_2d());
@@ -57,13 +101,35 @@ fn setup(mut cmd: Commands) {
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 enum AppState {
     Menu,
+    /// 加载谱面
+    Loading,
     Game,
     MakeMap,
+    Settings,
+    /// 一首歌结束后的成绩
+    Results,
+    /// 练习设置
+    Practice,
+    /// 双人对战的谱面选择
+    Versus,
 }
 
-/// 返回菜单
-fn back_menu(mut state: ResMut<State<AppState>>, key_input: Res<Input<KeyCode>>) {
+/// 返回菜单，从编辑器试玩时返回编辑器，练习时返回练习设置
+fn back_menu(
+    mut state: ResMut<State<AppState>>,
+    key_input: Res<Input<KeyCode>>,
+    playtest: Option<Res<Playtest>>,
+    practice: Option<Res<PracticeLoop>>,
+) {
     if key_input.just_pressed(KeyCode::Back) && state.current() != &AppState::Menu {
-        state.set(AppState::Menu).unwrap();
+        let in_game = state.current() == &AppState::Game;
+        let next = if playtest.is_some() && in_game {
+            AppState::MakeMap
+        } else if practice.is_some() && in_game {
+            AppState::Practice
+        } else {
+            AppState::Menu
+        };
+        state.set(next).unwrap();
     }
 }
@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+
+use super::*;
+use crate::analysis::{analyze_beat_grid, BeatGrid, DecodedAudio};
+
+/// 后台节拍分析
+#[derive(Default)]
+pub(super) struct BeatAnalysis {
+    task: Option<Task<Result<BeatGrid, String>>>,
+    /// 最近一次分析结果
+    result: Option<Result<BeatGrid, String>>,
+}
+
+impl BeatAnalysis {
+    /// 显示在编辑信息中的文本
+    pub(super) fn status(&self, presses: &Presses) -> String {
+        match (&self.task, &self.result, presses.bpm, presses.offset) {
+            (Some(_), ..) => "BPM: analyzing...".to_string(),
+            (None, Some(Err(e)), ..) => format!("BPM: {}", e),
+            (None, Some(Ok(grid)), ..) => format!(
+                "BPM: {:.2}. Offset: {:.3}s. Confidence: {:.0}%.",
+                grid.bpm,
+                grid.offset,
+                grid.confidence * 100.0
+            ),
+            (None, None, Some(bpm), offset) => {
+                format!("BPM: {:.2}. Offset: {:.3}s.", bpm, offset.unwrap_or(0.0))
+            }
+            (None, None, None, _) => "BPM: unknown (F6 to detect).".to_string(),
+        }
+    }
+}
+
+/// F6 开始分析当前歌曲的 BPM 和第一拍位置
+pub(super) fn start_beat_analysis(
+    key_input: Res<Input<KeyCode>>,
+    mut analysis: ResMut<BeatAnalysis>,
+    pool: Res<AsyncComputeTaskPool>,
+    chart: Res<EditingChart>,
+) {
+    if !key_input.just_pressed(KeyCode::F6) || analysis.task.is_some() {
+        return;
+    }
+    let path = chart.audio_path();
+    analysis.task = Some(pool.spawn(async move {
+        let audio = DecodedAudio::load(Path::new(&path))
+            .map_err(|e| format!("couldn't decode {}: {}", path, e))?;
+        analyze_beat_grid(&audio).ok_or_else(|| "no steady beat found".to_string())
+    }));
+}
+
+/// 分析完成后写入谱面的速度信息
+pub(super) fn finish_beat_analysis(
+    mut analysis: ResMut<BeatAnalysis>,
+    mut presses: ResMut<Presses>,
+) {
+    if analysis.task.is_none() {
+        return;
+    }
+    let task = analysis.task.as_mut().unwrap();
+    let result = match future::block_on(future::poll_once(task)) {
+        Some(result) => result,
+        None => return,
+    };
+    if let Ok(grid) = &result {
+        presses.bpm = Some(grid.bpm);
+        presses.offset = Some(grid.offset);
+    }
+    analysis.task = None;
+    analysis.result = Some(result);
+}
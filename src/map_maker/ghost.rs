@@ -0,0 +1,97 @@
+use super::*;
+
+/// 最多同时显示的幽灵箭头数量
+const GHOST_POOL_SIZE: usize = 48;
+/// 幽灵箭头大小
+const GHOST_SIZE: f32 = ARROW_SIZE * 0.6;
+
+/// 幽灵箭头材质：按速度区分颜色，选中的更亮
+pub(super) struct GhostMaterials {
+    normal: [Handle<ColorMaterial>; 3],
+    selected: [Handle<ColorMaterial>; 3],
+}
+
+impl FromWorld for GhostMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        let textures: Vec<Handle<Texture>> = ["red", "blue", "green"]
+            .iter()
+            .map(|color| asset_server.load(&*format!("images/arrow_{}.png", color)))
+            .collect();
+        let mut material = |index: usize, alpha: f32| {
+            materials.add(ColorMaterial {
+                color: Color::rgba(1.0, 1.0, 1.0, alpha),
+                texture: Some(textures[index].clone()),
+            })
+        };
+        Self {
+            normal: [material(0, 0.35), material(1, 0.35), material(2, 0.35)],
+            selected: [material(0, 0.9), material(1, 0.9), material(2, 0.9)],
+        }
+    }
+}
+
+impl GhostMaterials {
+    fn get(&self, speed: Speed, selected: bool) -> Handle<ColorMaterial> {
+        let index = match speed {
+            Speed::Slow => 0,
+            Speed::Medium => 1,
+            Speed::Fast => 2,
+        };
+        if selected {
+            self.selected[index].clone()
+        } else {
+            self.normal[index].clone()
+        }
+    }
+}
+
+/// 幽灵箭头：回放谱面中已有的箭头
+pub(super) struct GhostArrow;
+
+pub(super) fn setup_ghosts(mut cmd: Commands, materials: Res<GhostMaterials>) {
+    for _ in 0..GHOST_POOL_SIZE {
+        cmd.spawn_bundle(SpriteBundle {
+            material: materials.get(Speed::Slow, false),
+            sprite: Sprite::new(Vec2::new(GHOST_SIZE, GHOST_SIZE)),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(GhostArrow);
+    }
+}
+
+/// 把当前时间附近的箭头画在时间轴上，到达编辑器箭头位置时就是它的点击时间
+pub(super) fn update_ghosts(
+    time: Res<ControlledTime>,
+    presses: Res<Presses>,
+    selected: Res<SelectedNote>,
+    materials: Res<GhostMaterials>,
+    q: Query<(&mut Transform, &mut Visible, &mut Handle<ColorMaterial>), With<GhostArrow>>,
+) {
+    let now = time.seconds_since_startup();
+    let window = (WINDOW_WIDTH / 2.0 / PIXELS_PER_SECOND) as f64;
+    let arrows = &presses.arrows;
+    let start = arrows.partition_point(|a| a.click_time < now - window);
+    let end = arrows.partition_point(|a| a.click_time <= now + window);
+    let range = selected.range(arrows.len());
+    let mut visible_arrows = (start..end).map(|i| (i, &arrows[i]));
+    q.for_each_mut(
+        |(mut transform, mut visible, mut material)| match visible_arrows.next() {
+            Some((i, arrow)) => {
+                let x = (now - arrow.click_time) as f32 * PIXELS_PER_SECOND;
+                *transform = Transform::from_translation(Vec3::new(x, arrow.direction.y(), 0.5));
+                transform.rotate(Quat::from_rotation_z(arrow.direction.rotation()));
+                let is_selected = range.as_ref().map_or(false, |r| r.contains(&i));
+                *material = materials.get(arrow.speed, is_selected);
+                visible.is_visible = true;
+            }
+            None => visible.is_visible = false,
+        },
+    );
+}
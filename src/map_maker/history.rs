@@ -0,0 +1,132 @@
+use crate::types::ArrowTimeToml;
+
+/// 编辑历史（撤销/重做），每一步保存编辑前的箭头快照
+#[derive(Default)]
+pub struct EditHistory {
+    undo_stack: Vec<Vec<ArrowTimeToml>>,
+    redo_stack: Vec<Vec<ArrowTimeToml>>,
+    /// 是否处于一次连续录制中
+    recording: bool,
+}
+
+impl EditHistory {
+    /// 编辑前调用，保存当前快照
+    pub fn push(&mut self, arrows: &[ArrowTimeToml]) {
+        self.recording = false;
+        self.undo_stack.push(arrows.to_vec());
+        self.redo_stack.clear();
+    }
+    /// 录制按键前调用，一次连续录制只保存一次快照
+    pub fn begin_recording(&mut self, arrows: &[ArrowTimeToml]) {
+        if !self.recording {
+            self.push(arrows);
+            self.recording = true;
+        }
+    }
+    /// 结束当前录制，之后的录制算作新的一步
+    pub fn end_recording(&mut self) {
+        self.recording = false;
+    }
+    /// 撤销，返回是否有改动
+    pub fn undo(&mut self, arrows: &mut Vec<ArrowTimeToml>) -> bool {
+        self.recording = false;
+        match self.undo_stack.pop() {
+            Some(snapshot) => {
+                self.redo_stack.push(std::mem::replace(arrows, snapshot));
+                true
+            }
+            None => false,
+        }
+    }
+    /// 重做，返回是否有改动
+    pub fn redo(&mut self, arrows: &mut Vec<ArrowTimeToml>) -> bool {
+        self.recording = false;
+        match self.redo_stack.pop() {
+            Some(snapshot) => {
+                self.undo_stack.push(std::mem::replace(arrows, snapshot));
+                true
+            }
+            None => false,
+        }
+    }
+    /// 可撤销步数
+    pub fn undo_len(&self) -> usize {
+        self.undo_stack.len()
+    }
+    /// 可重做步数
+    pub fn redo_len(&self) -> usize {
+        self.redo_stack.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Directions, Speed};
+
+    fn arrow(click_time: f64) -> ArrowTimeToml {
+        ArrowTimeToml {
+            click_time,
+            speed: Speed::Medium,
+            direction: Directions::Up,
+            keysound: None,
+        }
+    }
+
+    fn times(arrows: &[ArrowTimeToml]) -> Vec<f64> {
+        arrows.iter().map(|arrow| arrow.click_time).collect()
+    }
+
+    #[test]
+    fn undo_then_redo_restores_the_notes() {
+        let mut history = EditHistory::default();
+        let mut arrows = vec![arrow(1.0)];
+        history.push(&arrows);
+        arrows.push(arrow(2.0));
+
+        assert!(history.undo(&mut arrows));
+        assert_eq!(times(&arrows), vec![1.0]);
+        assert!(history.redo(&mut arrows));
+        assert_eq!(times(&arrows), vec![1.0, 2.0]);
+        assert!(!history.redo(&mut arrows));
+        assert_eq!(history.undo_len(), 1);
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut history = EditHistory::default();
+        let mut arrows = vec![arrow(1.0)];
+        history.push(&arrows);
+        arrows.push(arrow(2.0));
+        history.undo(&mut arrows);
+        assert_eq!(history.redo_len(), 1);
+
+        history.push(&arrows);
+        arrows.push(arrow(3.0));
+        assert_eq!(history.redo_len(), 0);
+        assert!(!history.redo(&mut arrows));
+        assert_eq!(times(&arrows), vec![1.0, 3.0]);
+    }
+
+    #[test]
+    fn one_recording_pass_is_one_undo_step() {
+        let mut history = EditHistory::default();
+        let mut arrows = Vec::new();
+        for time in [1.0, 2.0, 3.0].iter() {
+            history.begin_recording(&arrows);
+            arrows.push(arrow(*time));
+        }
+        history.end_recording();
+        assert_eq!(history.undo_len(), 1);
+
+        // 结束后再录制是新的一步
+        history.begin_recording(&arrows);
+        arrows.push(arrow(4.0));
+        assert_eq!(history.undo_len(), 2);
+
+        history.undo(&mut arrows);
+        assert_eq!(times(&arrows), vec![1.0, 2.0, 3.0]);
+        history.undo(&mut arrows);
+        assert!(arrows.is_empty());
+    }
+}
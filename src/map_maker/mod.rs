@@ -0,0 +1,458 @@
+use std::ops::RangeInclusive;
+
+use bevy::prelude::*;
+
+use beat::*;
+use ghost::*;
+use history::EditHistory;
+use playback::*;
+pub use playtest::Playtest;
+use playtest::*;
+pub use session::MapMakerSession;
+use session::*;
+use waveform::*;
+
+use crate::chart::despawn_reload_prompt;
+use crate::consts::{ARROW_SIZE, WINDOW_WIDTH};
+use crate::time::ControlledTime;
+use crate::types::{ArrowTimeToml, Directions, SongConfigToml, Speed};
+use crate::AppState;
+
+mod beat;
+mod ghost;
+mod history;
+mod playback;
+mod playtest;
+mod session;
+mod waveform;
+
+/// 微调箭头时间的步长（秒）
+const NUDGE_STEP: f64 = 0.01;
+/// 按住 shift 时的微调步长（秒）
+const NUDGE_STEP_LARGE: f64 = 0.1;
+/// 编辑器时间轴上每秒对应的像素（波形和幽灵箭头）
+const PIXELS_PER_SECOND: f32 = 200.0;
+
+pub struct MapMakerPlugin;
+
+impl Plugin for MapMakerPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Presses>()
+            .init_resource::<EditingChart>()
+            .init_resource::<EditHistory>()
+            .init_resource::<SelectedNote>()
+            .init_resource::<RecordSpeed>()
+            .init_resource::<BeatAnalysis>()
+            .init_resource::<Waveform>()
+            .init_resource::<WaveformTextures>()
+            .init_resource::<GhostMaterials>()
+            .init_resource::<EditorMusic>()
+            .add_system_set(
+                SystemSet::on_enter(AppState::MakeMap)
+                    .with_system(start_session.system().label("session"))
+                    .with_system(setup_map_maker.system())
+                    .with_system(start_song.system().label("start_song").after("session"))
+                    .with_system(
+                        end_playtest
+                            .system()
+                            .after("start_song")
+                            .after("reset_time"),
+                    )
+                    .with_system(load_waveform.system().after("session"))
+                    .with_system(setup_waveform.system())
+                    .with_system(setup_ghosts.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::MakeMap)
+                    .with_system(save_key_presses.system())
+                    .with_system(switch_record_speed.system())
+                    .with_system(edit_notes.system())
+                    .with_system(undo_redo.system())
+                    .with_system(control_playback.system())
+                    .with_system(save_to_file.system())
+                    .with_system(start_playtest.system())
+                    .with_system(detect_changed_chart.system())
+                    .with_system(reload_changed_chart.system())
+                    .with_system(start_beat_analysis.system())
+                    .with_system(finish_beat_analysis.system())
+                    .with_system(finish_waveform.system())
+                    .with_system(toggle_spectrogram.system())
+                    .with_system(update_waveform.system())
+                    .with_system(update_ghosts.system())
+                    .with_system(toggle_map_maker_arrows.system())
+                    .with_system(update_map_maker_text.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::MakeMap)
+                    .with_system(despawn_map_maker.system())
+                    .with_system(despawn_reload_prompt.system())
+                    .with_system(stop_song.system()),
+            );
+    }
+}
+
+/// 按键录制，箭头按时间排序
+#[derive(Default)]
+struct Presses {
+    /// 每分钟节拍数
+    bpm: Option<f64>,
+    /// 第一拍的时间（秒）
+    offset: Option<f64>,
+    arrows: Vec<ArrowTimeToml>,
+}
+struct MapMakeArrow(Directions);
+
+/// 当前选中的箭头（在 Presses 中的下标）
+/// cursor 是当前箭头，anchor 是范围选择的起点
+#[derive(Default)]
+struct SelectedNote {
+    cursor: Option<usize>,
+    anchor: Option<usize>,
+}
+
+impl SelectedNote {
+    /// 选中上一个，extend 为 true 时扩展选择范围
+    fn prev(&mut self, len: usize, extend: bool) {
+        let cursor = match self.cursor {
+            _ if len == 0 => None,
+            Some(i) if i > 0 => Some((i - 1).min(len - 1)),
+            _ => Some(0),
+        };
+        self.move_cursor(cursor, extend);
+    }
+    /// 选中下一个，extend 为 true 时扩展选择范围
+    fn next(&mut self, len: usize, extend: bool) {
+        let cursor = match self.cursor {
+            _ if len == 0 => None,
+            Some(i) => Some((i + 1).min(len - 1)),
+            None => Some(0),
+        };
+        self.move_cursor(cursor, extend);
+    }
+    fn move_cursor(&mut self, cursor: Option<usize>, extend: bool) {
+        if !extend || self.anchor.is_none() {
+            self.anchor = cursor;
+        }
+        self.cursor = cursor;
+    }
+    /// 只选中一个箭头
+    fn select(&mut self, index: usize) {
+        self.cursor = Some(index);
+        self.anchor = Some(index);
+    }
+    /// 选中范围（包含两端）
+    fn range(&self, len: usize) -> Option<RangeInclusive<usize>> {
+        match (self.anchor, self.cursor) {
+            (Some(a), Some(c)) if a < len && c < len => Some(a.min(c)..=a.max(c)),
+            _ => None,
+        }
+    }
+    /// 箭头数量变化后修正下标
+    fn clamp(&mut self, len: usize) {
+        if len == 0 {
+            self.cursor = None;
+            self.anchor = None;
+        } else {
+            self.cursor = self.cursor.map(|i| i.min(len - 1));
+            self.anchor = self.anchor.map(|i| i.min(len - 1));
+        }
+    }
+}
+
+/// 录制时使用的箭头速度
+struct RecordSpeed(Speed);
+
+impl Default for RecordSpeed {
+    fn default() -> Self {
+        Self(Speed::Slow)
+    }
+}
+
+/// 数字键 1/2/3 对应的速度
+fn speed_key_just_pressed(key_input: &Input<KeyCode>) -> Option<Speed> {
+    if key_input.just_pressed(KeyCode::Key1) {
+        Some(Speed::Slow)
+    } else if key_input.just_pressed(KeyCode::Key2) {
+        Some(Speed::Medium)
+    } else if key_input.just_pressed(KeyCode::Key3) {
+        Some(Speed::Fast)
+    } else {
+        None
+    }
+}
+
+/// ctrl 是否按下
+fn ctrl_pressed(key_input: &Input<KeyCode>) -> bool {
+    key_input.pressed(KeyCode::LControl) || key_input.pressed(KeyCode::RControl)
+}
+
+/// 按键录制，叠加录制时跳过和已有箭头重复的按键
+fn save_key_presses(
+    key_input: Res<Input<KeyCode>>,
+    mut presses: ResMut<Presses>,
+    mut history: ResMut<EditHistory>,
+    record_speed: Res<RecordSpeed>,
+    time: Res<ControlledTime>,
+) {
+    let directions = Directions::directions();
+    for direction in directions {
+        if direction.key_just_pressed(&key_input) {
+            let click_time = time.seconds_since_startup();
+            let duplicate = presses.arrows.iter().any(|a| {
+                a.direction == direction && (a.click_time - click_time).abs() < DUPLICATE_WINDOW
+            });
+            if duplicate {
+                continue;
+            }
+            // 一次连续录制算作一步撤销
+            history.begin_recording(&presses.arrows);
+            let index = presses
+                .arrows
+                .partition_point(|a| a.click_time <= click_time);
+            presses.arrows.insert(
+                index,
+                ArrowTimeToml {
+                    click_time,
+                    speed: record_speed.0,
+                    direction,
+                    keysound: None,
+                },
+            );
+        }
+    }
+}
+
+/// 切换录制速度(1/2/3)
+fn switch_record_speed(key_input: Res<Input<KeyCode>>, mut record_speed: ResMut<RecordSpeed>) {
+    if ctrl_pressed(&key_input) {
+        return;
+    }
+    if let Some(speed) = speed_key_just_pressed(&key_input) {
+        record_speed.0 = speed;
+    }
+}
+
+/// 编辑箭头：选择、删除、移动、修改速度
+fn edit_notes(
+    key_input: Res<Input<KeyCode>>,
+    mut presses: ResMut<Presses>,
+    mut history: ResMut<EditHistory>,
+    mut selected: ResMut<SelectedNote>,
+) {
+    let len = presses.arrows.len();
+    let shift = key_input.pressed(KeyCode::LShift) || key_input.pressed(KeyCode::RShift);
+    // [ ] 切换选中，按住 shift 扩展选择范围
+    if key_input.just_pressed(KeyCode::LBracket) {
+        selected.prev(len, shift);
+    }
+    if key_input.just_pressed(KeyCode::RBracket) {
+        selected.next(len, shift);
+    }
+    let (range, index) = match (selected.range(len), selected.cursor) {
+        (Some(range), Some(index)) => (range, index),
+        _ => return,
+    };
+    let step = if shift { NUDGE_STEP_LARGE } else { NUDGE_STEP };
+    if key_input.just_pressed(KeyCode::Delete) {
+        // 删除选中范围
+        history.push(&presses.arrows);
+        let start = *range.start();
+        presses.arrows.drain(range);
+        selected.select(start);
+        selected.clamp(presses.arrows.len());
+    } else if key_input.just_pressed(KeyCode::Comma) || key_input.just_pressed(KeyCode::Period) {
+        // , . 提前/延后当前箭头
+        let delta = if key_input.just_pressed(KeyCode::Comma) {
+            -step
+        } else {
+            step
+        };
+        history.push(&presses.arrows);
+        let mut arrow = presses.arrows.remove(index);
+        arrow.click_time = (arrow.click_time + delta).max(0.0);
+        // 保持按时间排序
+        let new_index = presses
+            .arrows
+            .iter()
+            .position(|a| a.click_time > arrow.click_time)
+            .unwrap_or(len - 1);
+        presses.arrows.insert(new_index, arrow);
+        selected.select(new_index);
+    } else if key_input.just_pressed(KeyCode::Tab) {
+        // 切换当前箭头速度
+        history.push(&presses.arrows);
+        let arrow = &mut presses.arrows[index];
+        arrow.speed = arrow.speed.next();
+    } else if let Some(speed) =
+        speed_key_just_pressed(&key_input).filter(|_| ctrl_pressed(&key_input))
+    {
+        // ctrl + 1/2/3 修改选中范围的速度
+        history.push(&presses.arrows);
+        presses.arrows[range]
+            .iter_mut()
+            .for_each(|arrow| arrow.speed = speed);
+    }
+}
+
+/// 撤销(ctrl + z) / 重做(ctrl + y)
+fn undo_redo(
+    key_input: Res<Input<KeyCode>>,
+    mut presses: ResMut<Presses>,
+    mut history: ResMut<EditHistory>,
+    mut selected: ResMut<SelectedNote>,
+) {
+    if !ctrl_pressed(&key_input) {
+        return;
+    }
+    let changed = if key_input.just_pressed(KeyCode::Z) {
+        history.undo(&mut presses.arrows)
+    } else if key_input.just_pressed(KeyCode::Y) {
+        history.redo(&mut presses.arrows)
+    } else {
+        false
+    };
+    if changed {
+        selected.clamp(presses.arrows.len());
+    }
+}
+
+/// 保存到文件
+fn save_to_file(
+    key_input: Res<Input<KeyCode>>,
+    presses: Res<Presses>,
+    chart: Res<EditingChart>,
+    mut state: ResMut<State<AppState>>,
+) {
+    // ctrl + s
+    if key_input.pressed(KeyCode::LControl) && key_input.just_pressed(KeyCode::F) {
+        let config = SongConfigToml {
+            bpm: presses.bpm,
+            offset: presses.offset,
+            arrows: presses.arrows.clone(),
+            ..chart.config.clone()
+        };
+        // 按键序列化为toml格式
+        let toml_text = toml::to_string_pretty(&config).expect("couldn't convert to toml text");
+        // 保存文件
+        let path = format!("assets/songs/{}", chart.chart_file);
+        std::fs::write(&path, toml_text).expect("couldn't write chart");
+        // 返回menu
+        state.set(AppState::Menu).unwrap();
+    }
+}
+// 初始化
+fn setup_map_maker(
+    mut cmd: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+) {
+    let border_material = materials.add(asset_server.load("images/arrow_border.png").into());
+    let directions = Directions::directions();
+    for direction in directions {
+        let y = direction.y();
+        let mut transform = Transform::from_translation(Vec3::new(0.0, y, 1.0));
+        transform.rotate(Quat::from_rotation_z(direction.rotation()));
+        cmd.spawn_bundle(SpriteBundle {
+            material: border_material.clone(),
+            sprite: Sprite::new(Vec2::new(ARROW_SIZE, ARROW_SIZE)),
+            transform,
+            ..Default::default()
+        })
+        .insert(MapMakeArrow(direction));
+    }
+    // 编辑信息文本
+    let font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+    cmd.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Px(10.0),
+                top: Val::Px(10.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 20.0,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    })
+    .insert(MapMakerText);
+}
+
+struct MapMakerText;
+/// 更新编辑信息文本
+fn update_map_maker_text(
+    presses: Res<Presses>,
+    history: Res<EditHistory>,
+    selected: Res<SelectedNote>,
+    record_speed: Res<RecordSpeed>,
+    analysis: Res<BeatAnalysis>,
+    music: Res<EditorMusic>,
+    mut text: Query<&mut Text, With<MapMakerText>>,
+) {
+    if !(presses.is_changed()
+        || history.is_changed()
+        || selected.is_changed()
+        || record_speed.is_changed()
+        || analysis.is_changed()
+        || music.is_changed())
+    {
+        return;
+    }
+    let len = presses.arrows.len();
+    let selected_arrow = selected
+        .cursor
+        .and_then(|i| presses.arrows.get(i).map(|a| (i, a)));
+    let mut selected_text = match selected_arrow {
+        Some((i, arrow)) => format!(
+            "#{} {:.2}s {:?} {:?}",
+            i, arrow.click_time, arrow.direction, arrow.speed
+        ),
+        None => "None".to_string(),
+    };
+    if let Some(range) = selected.range(len).filter(|r| r.start() != r.end()) {
+        selected_text += &format!(" (#{}..#{})", range.start(), range.end());
+    }
+    let paused = if music.paused { " Paused." } else { "" };
+    if let Ok(mut text) = text.single_mut() {
+        text.sections[0].value = format!(
+            "Speed: {:?}. Notes: {}. Undo: {}. Redo: {}.\nSelected: {}.\n{}\nVolume: {:.0}%.{}",
+            record_speed.0,
+            len,
+            history.undo_len(),
+            history.redo_len(),
+            selected_text,
+            analysis.status(&presses),
+            music.volume * 100.0,
+            paused,
+        );
+    }
+}
+
+type MapMakerEntity = Or<(
+    With<MapMakeArrow>,
+    With<MapMakerText>,
+    With<WaveformStrip>,
+    With<GhostArrow>,
+)>;
+// 销毁
+fn despawn_map_maker(mut cmd: Commands, q: Query<Entity, MapMakerEntity>) {
+    q.for_each(|e| cmd.entity(e).despawn_recursive());
+}
+
+// 按键点击时才显示
+fn toggle_map_maker_arrows(
+    key_input: Res<Input<KeyCode>>,
+    q: Query<(&mut Visible, &MapMakeArrow)>,
+) {
+    q.for_each_mut(|(mut visible, arrow)| {
+        visible.is_visible = arrow.0.key_just_pressed(&key_input);
+    });
+}
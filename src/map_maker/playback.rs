@@ -0,0 +1,106 @@
+use super::*;
+use crate::audio::{normalization_gain, AudioChannel, AudioPlayer, SoundHandle};
+use crate::settings::Settings;
+
+/// PageUp / PageDown 跳转的步长（秒）
+const SEEK_STEP: f64 = 5.0;
+/// 音量调整步长
+const VOLUME_STEP: f32 = 0.1;
+
+/// 编辑器中正在播放的歌曲
+pub(super) struct EditorMusic {
+    pub(super) handle: Option<SoundHandle>,
+    pub(super) paused: bool,
+    pub(super) volume: f32,
+}
+
+impl Default for EditorMusic {
+    fn default() -> Self {
+        Self {
+            handle: None,
+            paused: false,
+            volume: 1.0,
+        }
+    }
+}
+
+pub(super) fn start_song(
+    mut player: ResMut<AudioPlayer>,
+    mut music: ResMut<EditorMusic>,
+    chart: Res<EditingChart>,
+    settings: Res<Settings>,
+) {
+    let handle = player.play(chart.audio.clone(), AudioChannel::Music);
+    player.set_volume(handle, normalization_gain(chart.config.loudness));
+    music.handle = Some(handle);
+    music.paused = false;
+    music.volume = settings.volume.music;
+}
+
+pub(super) fn stop_song(
+    mut player: ResMut<AudioPlayer>,
+    mut music: ResMut<EditorMusic>,
+    mut history: ResMut<EditHistory>,
+) {
+    if let Some(handle) = music.handle.take() {
+        player.stop(handle);
+    }
+    // 停止后的录制算作新的一步
+    history.end_recording();
+}
+
+/// 空格暂停/继续，PageUp / PageDown 后退/前进，Home 回到开头，- = 调整音乐音量
+pub(super) fn control_playback(
+    key_input: Res<Input<KeyCode>>,
+    mut player: ResMut<AudioPlayer>,
+    mut music: ResMut<EditorMusic>,
+    mut time: ResMut<ControlledTime>,
+    mut history: ResMut<EditHistory>,
+    mut settings: ResMut<Settings>,
+) {
+    let handle = match music.handle {
+        Some(handle) => handle,
+        None => return,
+    };
+    if key_input.just_pressed(KeyCode::Space) {
+        let paused = !player.is_paused(handle);
+        if paused {
+            player.pause(handle);
+        } else {
+            player.resume(handle);
+        }
+        time.set_paused(paused);
+        music.paused = paused;
+        // 暂停或继续后重新开始一次连续录制
+        history.end_recording();
+    }
+    let now = time.seconds_since_startup();
+    let seek_to = if key_input.just_pressed(KeyCode::PageUp) {
+        Some(now - SEEK_STEP)
+    } else if key_input.just_pressed(KeyCode::PageDown) {
+        Some(now + SEEK_STEP)
+    } else if key_input.just_pressed(KeyCode::Home) {
+        Some(0.0)
+    } else {
+        None
+    };
+    if let Some(position) = seek_to {
+        let position = position.max(0.0);
+        time.seek(position);
+        player.seek(handle, position);
+        // 跳转后重新开始一次连续录制
+        history.end_recording();
+    }
+    let volume_delta = if key_input.just_pressed(KeyCode::Minus) {
+        -VOLUME_STEP
+    } else if key_input.just_pressed(KeyCode::Equals) {
+        VOLUME_STEP
+    } else {
+        0.0
+    };
+    if volume_delta != 0.0 {
+        // 和设置界面的音乐音量相同，修改后会保存
+        music.volume = (music.volume + volume_delta).max(0.0).min(1.0);
+        settings.volume.music = music.volume;
+    }
+}
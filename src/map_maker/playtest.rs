@@ -0,0 +1,84 @@
+use super::*;
+use crate::audio::{normalization_gain, AudioPlayer};
+use crate::consts::DELAY_SONG;
+use crate::time::GameStartTime;
+use crate::types::SongConfig;
+
+/// 试玩时在编辑位置之前预留的时间（秒）
+const PLAYTEST_LEAD_IN: f64 = 3.0;
+/// 在第一个箭头的生成时间之前多开始这么久（秒）
+const SPAWN_MARGIN: f64 = 0.1;
+
+/// 正在从编辑器试玩，退出试玩时返回编辑器
+pub struct Playtest {
+    /// 开始试玩时编辑器的时间（秒），返回编辑器时回到这里
+    editor_time: f64,
+}
+
+/// F5 从当前编辑位置开始试玩
+pub(super) fn start_playtest(
+    mut cmd: Commands,
+    key_input: Res<Input<KeyCode>>,
+    presses: Res<Presses>,
+    selected: Res<SelectedNote>,
+    time: Res<ControlledTime>,
+    chart: Res<EditingChart>,
+    mut state: ResMut<State<AppState>>,
+) {
+    if !key_input.just_pressed(KeyCode::F5) {
+        return;
+    }
+    // 编辑位置：选中箭头的时间，没有选中则是当前时间
+    let position = selected
+        .cursor
+        .and_then(|i| presses.arrows.get(i))
+        .map_or(time.seconds_since_startup(), |arrow| arrow.click_time);
+    let mut config = SongConfig::new(
+        "Playtest".to_string(),
+        chart.audio.clone(),
+        &presses.arrows,
+        chart.sounds.clone(),
+    );
+    config.volume = normalization_gain(chart.config.loudness);
+    let start = position - PLAYTEST_LEAD_IN;
+    let start_time = if start > 0.0 {
+        // 跳过开始时间之前点击的箭头
+        config.arrows.retain(|arrow| arrow.click_time() >= start);
+        // 生成时间在开始时间之前的箭头也要生成，从最早的生成时间之前开始，
+        // 保证第一帧之后才到生成时间
+        let start = config
+            .arrows
+            .first()
+            .map_or(start, |arrow| arrow.spawn_time.min(start) - SPAWN_MARGIN);
+        (start + DELAY_SONG).max(0.0)
+    } else {
+        0.0
+    };
+    cmd.insert_resource(GameStartTime(start_time));
+    cmd.insert_resource(config);
+    cmd.insert_resource(Playtest {
+        editor_time: time.seconds_since_startup(),
+    });
+    state.set(AppState::Game).unwrap();
+}
+
+/// 试玩结束回到编辑器，时间和歌曲回到开始试玩时的位置
+pub(super) fn end_playtest(
+    mut cmd: Commands,
+    playtest: Option<Res<Playtest>>,
+    mut start_time: ResMut<GameStartTime>,
+    mut time: ResMut<ControlledTime>,
+    mut player: ResMut<AudioPlayer>,
+    music: Res<EditorMusic>,
+) {
+    let playtest = match playtest {
+        Some(playtest) => playtest,
+        None => return,
+    };
+    time.seek(playtest.editor_time);
+    if let Some(handle) = music.handle {
+        player.seek(handle, playtest.editor_time);
+    }
+    cmd.remove_resource::<Playtest>();
+    start_time.0 = 0.0;
+}
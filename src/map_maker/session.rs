@@ -0,0 +1,206 @@
+use super::*;
+use crate::chart::{chart_modified, spawn_reload_prompt, ReloadPrompt};
+use crate::types::{chart_asset_path, SongConfigToml, SongSounds};
+
+/// 新谱面使用的歌曲（相对于 assets/songs）
+const NEW_CHART_SONG: &str = "../map_maker_song.mp3";
+/// 新谱面保存的文件（相对于 assets/songs）
+const NEW_CHART_FILE: &str = "map.toml";
+/// 同一方向上间隔小于该值（秒）的按键视为重复
+pub(super) const DUPLICATE_WINDOW: f64 = 0.05;
+
+/// 进入编辑器时的选择：新建谱面，或者在已有谱面上叠加录制
+pub enum MapMakerSession {
+    New,
+    /// 已加载的谱面，chart_file 相对于 assets/songs
+    Overdub {
+        chart_file: String,
+        chart: Handle<SongConfigToml>,
+    },
+}
+
+/// 正在编辑的谱面
+pub(super) struct EditingChart {
+    /// 谱面的名字、音频、预览位置、响度、音效等信息，保存时原样写回，箭头为空
+    pub(super) config: SongConfigToml,
+    /// 保存的谱面文件（相对于 assets/songs）
+    pub(super) chart_file: String,
+    pub(super) audio: Handle<AudioSource>,
+    /// 试玩用的已加载音效
+    pub(super) sounds: SongSounds,
+    /// 叠加录制的谱面资源，用于发现谱面在磁盘上被修改
+    handle: Option<Handle<SongConfigToml>>,
+}
+
+impl EditingChart {
+    /// 新谱面
+    fn new(asset_server: &AssetServer) -> Self {
+        let config = SongConfigToml {
+            name: "map".to_string(),
+            filename: NEW_CHART_SONG.to_string(),
+            ..Default::default()
+        };
+        Self::from_config(config, NEW_CHART_FILE.to_string(), None, asset_server)
+    }
+    /// 载入已有谱面：保留谱面信息，使用谱面声明的音效
+    fn from_config(
+        config: SongConfigToml,
+        chart_file: String,
+        handle: Option<Handle<SongConfigToml>>,
+        asset_server: &AssetServer,
+    ) -> Self {
+        let audio = asset_server.load(&*format!(
+            "songs/{}",
+            chart_asset_path(&chart_file, &config.filename)
+        ));
+        let sounds = SongSounds::load(
+            &config.hit_sounds,
+            &config.keysounds,
+            &chart_file,
+            asset_server,
+        );
+        Self {
+            config,
+            chart_file,
+            audio,
+            sounds,
+            handle,
+        }
+    }
+    /// 音频文件路径
+    pub(super) fn audio_path(&self) -> String {
+        format!(
+            "assets/songs/{}",
+            chart_asset_path(&self.chart_file, &self.config.filename)
+        )
+    }
+}
+
+impl FromWorld for EditingChart {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+        Self::new(asset_server)
+    }
+}
+
+/// 开始新的编辑：清空上一次的状态，叠加录制时载入已有谱面
+/// 从试玩返回时没有 MapMakerSession，保留编辑状态
+pub(super) fn start_session(
+    mut cmd: Commands,
+    session: Option<Res<MapMakerSession>>,
+    asset_server: Res<AssetServer>,
+    charts: Res<Assets<SongConfigToml>>,
+    mut chart: ResMut<EditingChart>,
+    mut presses: ResMut<Presses>,
+) {
+    let session = match session {
+        Some(session) => session,
+        None => return,
+    };
+    // 先清空上一次的录制，谱面没有加载成功时按新谱面编辑
+    *presses = Presses::default();
+    match &*session {
+        MapMakerSession::New => {
+            *chart = EditingChart::new(&asset_server);
+        }
+        MapMakerSession::Overdub {
+            chart_file,
+            chart: handle,
+        } => match charts.get(handle) {
+            Some(parsed) => open_chart(
+                &mut chart,
+                &mut presses,
+                parsed.clone(),
+                chart_file,
+                handle,
+                &asset_server,
+            ),
+            None => {
+                eprintln!("couldn't load {}, starting a new chart", chart_file);
+                *chart = EditingChart::new(&asset_server);
+            }
+        },
+    }
+    reset_editing(&mut cmd);
+    cmd.remove_resource::<MapMakerSession>();
+}
+
+/// 载入已有谱面，箭头按时间排序
+fn open_chart(
+    chart: &mut EditingChart,
+    presses: &mut Presses,
+    mut parsed: SongConfigToml,
+    chart_file: &str,
+    handle: &Handle<SongConfigToml>,
+    asset_server: &AssetServer,
+) {
+    let mut arrows = std::mem::take(&mut parsed.arrows);
+    arrows.sort_by(|a, b| a.click_time.partial_cmp(&b.click_time).unwrap());
+    *presses = Presses {
+        bpm: parsed.bpm,
+        offset: parsed.offset,
+        arrows,
+    };
+    *chart = EditingChart::from_config(
+        parsed,
+        chart_file.to_string(),
+        Some(handle.clone()),
+        asset_server,
+    );
+}
+
+/// 清空撤销记录、选择和节拍分析
+fn reset_editing(cmd: &mut Commands) {
+    cmd.insert_resource(EditHistory::default());
+    cmd.insert_resource(SelectedNote::default());
+    cmd.insert_resource(BeatAnalysis::default());
+}
+
+/// 叠加录制的谱面在磁盘上被修改时提示
+pub(super) fn detect_changed_chart(
+    mut cmd: Commands,
+    mut events: EventReader<AssetEvent<SongConfigToml>>,
+    chart: Res<EditingChart>,
+    asset_server: Res<AssetServer>,
+    prompt: Query<(), With<ReloadPrompt>>,
+) {
+    if prompt.iter().next().is_none() && chart_modified(&mut events, chart.handle.as_ref()) {
+        spawn_reload_prompt(
+            &mut cmd,
+            &asset_server,
+            "Chart changed on disk. Press F9 to reload it (discards unsaved edits).",
+        );
+    }
+}
+
+/// 按 F9 重新载入被修改的谱面
+pub(super) fn reload_changed_chart(
+    mut cmd: Commands,
+    key_input: Res<Input<KeyCode>>,
+    charts: Res<Assets<SongConfigToml>>,
+    asset_server: Res<AssetServer>,
+    prompt: Query<Entity, With<ReloadPrompt>>,
+    mut chart: ResMut<EditingChart>,
+    mut presses: ResMut<Presses>,
+) {
+    if !key_input.just_pressed(KeyCode::F9) {
+        return;
+    }
+    let (prompt, handle) = match (prompt.iter().next(), chart.handle.clone()) {
+        (Some(prompt), Some(handle)) => (prompt, handle),
+        _ => return,
+    };
+    if let Some(parsed) = charts.get(&handle) {
+        let chart_file = chart.chart_file.clone();
+        open_chart(
+            &mut chart,
+            &mut presses,
+            parsed.clone(),
+            &chart_file,
+            &handle,
+            &asset_server,
+        );
+        reset_editing(&mut cmd);
+    }
+    cmd.entity(prompt).despawn_recursive();
+}
@@ -0,0 +1,189 @@
+use std::io;
+use std::path::Path;
+
+use bevy::render::texture::{Extent3d, TextureDimension, TextureFormat};
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+
+use super::*;
+use crate::analysis::{WaveformPeaks, SPECTRUM_BANDS};
+use crate::consts::WINDOW_HEIGHT;
+
+/// 波形条的列数，每列对应一个峰值，当前时间在正中间
+const STRIP_COLUMNS: u32 = 400;
+/// 波形纹理高度
+const WAVEFORM_TEXTURE_HEIGHT: u32 = 64;
+/// 波形条显示高度
+const WAVEFORM_HEIGHT: f32 = 440.0;
+/// 频谱图显示高度
+const SPECTROGRAM_HEIGHT: f32 = 80.0;
+
+/// 编辑器歌曲的波形数据
+#[derive(Default)]
+pub(super) struct Waveform {
+    /// 波形对应的音频文件
+    path: String,
+    task: Option<Task<io::Result<WaveformPeaks>>>,
+    peaks: Option<WaveformPeaks>,
+    /// 是否显示频谱图
+    show_spectrogram: bool,
+}
+
+/// 波形条和频谱图的纹理
+pub(super) struct WaveformTextures {
+    waveform: Handle<Texture>,
+    spectrogram: Handle<Texture>,
+}
+
+impl FromWorld for WaveformTextures {
+    fn from_world(world: &mut World) -> Self {
+        let mut textures = world.get_resource_mut::<Assets<Texture>>().unwrap();
+        let mut texture = |height: u32| {
+            textures.add(Texture::new_fill(
+                Extent3d::new(STRIP_COLUMNS, height, 1),
+                TextureDimension::D2,
+                &[0, 0, 0, 0],
+                TextureFormat::Rgba8UnormSrgb,
+            ))
+        };
+        Self {
+            waveform: texture(WAVEFORM_TEXTURE_HEIGHT),
+            spectrogram: texture(SPECTRUM_BANDS as u32),
+        }
+    }
+}
+
+/// 波形条（在编辑器箭头下方）
+pub(super) struct WaveformStrip;
+/// 频谱图
+pub(super) struct SpectrogramStrip;
+
+/// 后台解码歌曲并计算波形，结果有缓存；换了歌曲时重新加载
+pub(super) fn load_waveform(
+    mut waveform: ResMut<Waveform>,
+    pool: Res<AsyncComputeTaskPool>,
+    chart: Res<EditingChart>,
+) {
+    let path = chart.audio_path();
+    if waveform.path == path && (waveform.peaks.is_some() || waveform.task.is_some()) {
+        return;
+    }
+    waveform.peaks = None;
+    waveform.path = path.clone();
+    // 替换旧任务会取消它
+    waveform.task =
+        Some(pool.spawn(async move { WaveformPeaks::load_or_compute(Path::new(&path)) }));
+}
+
+pub(super) fn finish_waveform(mut waveform: ResMut<Waveform>) {
+    if waveform.task.is_none() {
+        return;
+    }
+    let task = waveform.task.as_mut().unwrap();
+    let result = match future::block_on(future::poll_once(task)) {
+        Some(result) => result,
+        None => return,
+    };
+    waveform.task = None;
+    match result {
+        Ok(peaks) => waveform.peaks = Some(peaks),
+        Err(e) => eprintln!("couldn't load waveform of {}: {}", waveform.path, e),
+    }
+}
+
+pub(super) fn setup_waveform(
+    mut cmd: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    textures: Res<WaveformTextures>,
+    waveform: Res<Waveform>,
+) {
+    cmd.spawn_bundle(SpriteBundle {
+        material: materials.add(ColorMaterial::texture(textures.waveform.clone())),
+        sprite: Sprite::new(Vec2::new(WINDOW_WIDTH, WAVEFORM_HEIGHT)),
+        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.1)),
+        ..Default::default()
+    })
+    .insert(WaveformStrip);
+    let y = (SPECTROGRAM_HEIGHT - WINDOW_HEIGHT) / 2.0;
+    cmd.spawn_bundle(SpriteBundle {
+        material: materials.add(ColorMaterial::texture(textures.spectrogram.clone())),
+        sprite: Sprite::new(Vec2::new(WINDOW_WIDTH, SPECTROGRAM_HEIGHT)),
+        transform: Transform::from_translation(Vec3::new(0.0, y, 0.1)),
+        visible: Visible {
+            is_visible: waveform.show_spectrogram,
+            is_transparent: true,
+        },
+        ..Default::default()
+    })
+    .insert(WaveformStrip)
+    .insert(SpectrogramStrip);
+}
+
+/// F7 显示/隐藏频谱图
+pub(super) fn toggle_spectrogram(
+    key_input: Res<Input<KeyCode>>,
+    mut waveform: ResMut<Waveform>,
+    q: Query<&mut Visible, With<SpectrogramStrip>>,
+) {
+    if key_input.just_pressed(KeyCode::F7) {
+        waveform.show_spectrogram = !waveform.show_spectrogram;
+        let show = waveform.show_spectrogram;
+        q.for_each_mut(|mut visible| visible.is_visible = show);
+    }
+}
+
+/// 跟随时间滚动：左边是将要播放的部分，和游戏中箭头的移动方向一致
+pub(super) fn update_waveform(
+    time: Res<ControlledTime>,
+    waveform: Res<Waveform>,
+    waveform_textures: Res<WaveformTextures>,
+    mut textures: ResMut<Assets<Texture>>,
+) {
+    let peaks = match &waveform.peaks {
+        Some(peaks) => peaks,
+        None => return,
+    };
+    let now = time.seconds_since_startup();
+    let center = STRIP_COLUMNS / 2;
+    let column_width = WINDOW_WIDTH / STRIP_COLUMNS as f32;
+    let column_at = |x: u32| {
+        let pixels = (center as f32 - x as f32) * column_width;
+        let offset = (pixels / PIXELS_PER_SECOND) as f64;
+        peaks.column_at(now + offset)
+    };
+    if let Some(texture) = textures.get_mut(&waveform_textures.waveform) {
+        let height = WAVEFORM_TEXTURE_HEIGHT;
+        for x in 0..STRIP_COLUMNS {
+            let peak = column_at(x).map_or(0, |column| peaks.peaks[column]);
+            let half = peak as f32 / 255.0 * height as f32 / 2.0;
+            // 已经播放的部分颜色暗一些
+            let color = if x < center {
+                [120, 180, 255, 160]
+            } else {
+                [140, 140, 140, 100]
+            };
+            for y in 0..height {
+                let filled = (y as f32 + 0.5 - height as f32 / 2.0).abs() <= half;
+                let index = ((y * STRIP_COLUMNS + x) * 4) as usize;
+                let pixel = if filled { color } else { [0, 0, 0, 0] };
+                texture.data[index..index + 4].copy_from_slice(&pixel);
+            }
+        }
+    }
+    if !waveform.show_spectrogram {
+        return;
+    }
+    if let Some(texture) = textures.get_mut(&waveform_textures.spectrogram) {
+        for x in 0..STRIP_COLUMNS {
+            let bands = column_at(x).map(|column| peaks.bands(column));
+            for band in 0..SPECTRUM_BANDS {
+                let level = bands.map_or(0, |bands| bands[band]);
+                // 高频在上
+                let y = (SPECTRUM_BANDS - 1 - band) as u32;
+                let index = ((y * STRIP_COLUMNS + x) * 4) as usize;
+                let pixel = [level, level / 2, 96 + level / 3, 220];
+                texture.data[index..index + 4].copy_from_slice(&pixel);
+            }
+        }
+    }
+}
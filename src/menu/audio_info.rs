@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task};
+use futures_lite::future;
+
+use super::preview::PREVIEW_LENGTH;
+use crate::analysis::WaveformPeaks;
+
+/// 从音频波形得到的信息
+#[derive(Copy, Clone)]
+pub(super) struct AudioInfo {
+    /// 歌曲长度（秒）
+    pub(super) length: f64,
+    /// 最响的预览片段的开始时间
+    pub(super) loudest: f64,
+}
+
+/// 按音频路径缓存的歌曲信息，在后台计算，波形有磁盘缓存
+#[derive(Default)]
+pub(super) struct AudioInfoCache {
+    infos: HashMap<String, Option<AudioInfo>>,
+    tasks: HashMap<String, Task<Option<AudioInfo>>>,
+}
+
+impl AudioInfoCache {
+    /// 已计算的信息，没有时开始计算；音频无法解码时为 None
+    pub(super) fn get(&mut self, path: &str, pool: &AsyncComputeTaskPool) -> Option<AudioInfo> {
+        if let Some(info) = self.infos.get(path) {
+            return *info;
+        }
+        if !self.tasks.contains_key(path) {
+            let audio_path = path.to_string();
+            let task = pool.spawn(async move {
+                let peaks = WaveformPeaks::load_or_compute(Path::new(&audio_path)).ok()?;
+                Some(AudioInfo {
+                    length: peaks.duration(),
+                    loudest: peaks.loudest_section(PREVIEW_LENGTH),
+                })
+            });
+            self.tasks.insert(path.to_string(), task);
+        }
+        None
+    }
+    /// 已计算的信息，不开始计算
+    pub(super) fn cached(&self, path: &str) -> Option<AudioInfo> {
+        self.infos.get(path).copied().flatten()
+    }
+    /// 是否还在计算
+    pub(super) fn is_pending(&self, path: &str) -> bool {
+        self.tasks.contains_key(path)
+    }
+    /// 收集完成的计算
+    fn finish_tasks(&mut self) {
+        let finished: Vec<(String, Option<AudioInfo>)> = self
+            .tasks
+            .iter_mut()
+            .filter_map(|(path, task)| {
+                future::block_on(future::poll_once(task)).map(|info| (path.clone(), info))
+            })
+            .collect();
+        for (path, info) in finished {
+            self.tasks.remove(&path);
+            self.infos.insert(path, info);
+        }
+    }
+}
+
+pub(super) fn finish_audio_info(mut cache: ResMut<AudioInfoCache>) {
+    cache.finish_tasks();
+}
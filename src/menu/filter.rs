@@ -0,0 +1,265 @@
+use std::cmp::Ordering;
+
+use super::*;
+use crate::records::PlayRecords;
+
+/// 排序方式
+#[derive(Copy, Clone, PartialEq)]
+pub(super) enum SortKey {
+    Name,
+    Bpm,
+    Difficulty,
+    Length,
+    /// 最近玩过的在前
+    Recent,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 5] = [
+        SortKey::Name,
+        SortKey::Bpm,
+        SortKey::Difficulty,
+        SortKey::Length,
+        SortKey::Recent,
+    ];
+    fn name(&self) -> &'static str {
+        match self {
+            SortKey::Name => "Name",
+            SortKey::Bpm => "BPM",
+            SortKey::Difficulty => "Difficulty",
+            SortKey::Length => "Length",
+            SortKey::Recent => "Recently played",
+        }
+    }
+}
+
+/// 难度范围，没有标注难度的谱面只在 All 中显示
+#[derive(Copy, Clone, PartialEq)]
+pub(super) enum DifficultyRange {
+    All,
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl DifficultyRange {
+    const ALL: [DifficultyRange; 4] = [
+        DifficultyRange::All,
+        DifficultyRange::Easy,
+        DifficultyRange::Normal,
+        DifficultyRange::Hard,
+    ];
+    fn name(&self) -> &'static str {
+        match self {
+            DifficultyRange::All => "All",
+            DifficultyRange::Easy => "1-3",
+            DifficultyRange::Normal => "4-6",
+            DifficultyRange::Hard => "7-10",
+        }
+    }
+    fn contains(&self, difficulty: Option<u32>) -> bool {
+        let range = match self {
+            DifficultyRange::All => return true,
+            DifficultyRange::Easy => 1..=3,
+            DifficultyRange::Normal => 4..=6,
+            DifficultyRange::Hard => 7..=10,
+        };
+        difficulty.map_or(false, |difficulty| range.contains(&difficulty))
+    }
+}
+
+/// 下一个选项，到头后回到第一个
+pub(super) fn next<T: Copy + PartialEq>(all: &[T], current: T) -> T {
+    let i = all.iter().position(|&item| item == current).unwrap_or(0);
+    all[(i + 1) % all.len()]
+}
+
+/// 歌曲列表的搜索、排序和过滤
+pub(super) struct SongFilter {
+    /// 在名字、作者和歌曲包中搜索，不区分大小写
+    query: String,
+    sort: SortKey,
+    difficulty: DifficultyRange,
+    /// 只显示还没有通关的歌曲
+    uncleared_only: bool,
+}
+
+impl Default for SongFilter {
+    fn default() -> Self {
+        Self {
+            query: String::new(),
+            sort: SortKey::Name,
+            difficulty: DifficultyRange::All,
+            uncleared_only: false,
+        }
+    }
+}
+
+impl SongFilter {
+    pub(super) fn matches(&self, song: &SongEntry, records: &PlayRecords) -> bool {
+        let query = self.query.to_lowercase();
+        let chart = &song.chart;
+        let contains = |text: &Option<String>| {
+            text.as_ref()
+                .map_or(false, |text| text.to_lowercase().contains(&query))
+        };
+        let found = chart.name.to_lowercase().contains(&query)
+            || contains(&chart.artist)
+            || contains(&song.pack);
+        let cleared = records
+            .get(&song.chart_file())
+            .map_or(false, |record| record.cleared);
+        found && self.difficulty.contains(chart.difficulty) && !(self.uncleared_only && cleared)
+    }
+    /// 按排序方式比较，没有对应信息的排在最后，相同时按名字
+    pub(super) fn compare(
+        &self,
+        a: &SongEntry,
+        b: &SongEntry,
+        records: &PlayRecords,
+        audio_info: &AudioInfoCache,
+    ) -> Ordering {
+        let length = |song: &SongEntry| {
+            audio_info
+                .cached(&song.audio_path())
+                .map(|info| info.length)
+                .or(song.chart.last_click_time)
+        };
+        let last_played = |song: &SongEntry| {
+            records
+                .get(&song.chart_file())
+                .and_then(|record| record.last_played)
+                // 越近越靠前
+                .map(|time| -(time as f64))
+        };
+        let ordering = match self.sort {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Bpm => compare_missing_last(a.chart.bpm, b.chart.bpm),
+            SortKey::Difficulty => compare_missing_last(
+                a.chart.difficulty.map(f64::from),
+                b.chart.difficulty.map(f64::from),
+            ),
+            SortKey::Length => compare_missing_last(length(a), length(b)),
+            SortKey::Recent => compare_missing_last(last_played(a), last_played(b)),
+        };
+        ordering.then_with(|| a.cmp_by_name(b))
+    }
+}
+
+fn compare_missing_last(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// 搜索和过滤的状态文字
+pub(super) struct FilterText;
+
+pub(super) fn spawn_filter_bar(parent: &mut ChildBuilder, button_materials: &ButtonMaterials) {
+    parent
+        .spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect {
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text {
+                sections: vec![
+                    TextSection {
+                        value: String::new(),
+                        style: button_materials.text_style(),
+                    },
+                    TextSection {
+                        value: String::new(),
+                        style: TextStyle {
+                            font_size: 16.0,
+                            color: Color::rgb(0.6, 0.6, 0.6),
+                            ..button_materials.text_style()
+                        },
+                    },
+                ],
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(FilterText);
+}
+
+/// 输入文字搜索，退格删除，Delete 清空；Tab 切换排序，F2 切换难度范围，F3 只显示未通关
+pub(super) fn filter_keyboard(
+    mut received_characters: EventReader<ReceivedCharacter>,
+    key_input: Res<Input<KeyCode>>,
+    added: Query<(), Added<FilterText>>,
+    mut filter: ResMut<SongFilter>,
+) {
+    // 进入菜单的那一帧：丢掉在上一个界面输入的文字，返回菜单的退格也不删除文字
+    if added.iter().next().is_some() {
+        received_characters.iter().for_each(drop);
+        return;
+    }
+    for event in received_characters.iter() {
+        if !event.char.is_control() {
+            filter.query.push(event.char);
+        }
+    }
+    if key_input.just_pressed(KeyCode::Back) {
+        filter.query.pop();
+    }
+    if key_input.just_pressed(KeyCode::Delete) {
+        filter.query.clear();
+    }
+    if key_input.just_pressed(KeyCode::Tab) {
+        filter.sort = next(&SortKey::ALL, filter.sort);
+    }
+    if key_input.just_pressed(KeyCode::F2) {
+        filter.difficulty = next(&DifficultyRange::ALL, filter.difficulty);
+    }
+    if key_input.just_pressed(KeyCode::F3) {
+        filter.uncleared_only = !filter.uncleared_only;
+    }
+}
+
+/// 搜索或过滤变化后重新生成列表，保持选中的歌曲
+pub(super) fn filter_songs(
+    filter: Res<SongFilter>,
+    library: Res<SongLibrary>,
+    records: Res<PlayRecords>,
+    audio_info: Res<AudioInfoCache>,
+    mut wheel: ResMut<SongWheel>,
+) {
+    if !filter.is_changed() {
+        return;
+    }
+    let keep = wheel.selected_song(&library).map(|song| song.file.clone());
+    wheel.refresh(&library, &filter, &records, &audio_info, keep.as_deref());
+}
+
+pub(super) fn update_filter_text(
+    filter: Res<SongFilter>,
+    wheel: Res<SongWheel>,
+    added: Query<(), Added<FilterText>>,
+    texts: Query<&mut Text, With<FilterText>>,
+) {
+    if !(filter.is_changed() || wheel.is_changed() || added.iter().next().is_some()) {
+        return;
+    }
+    texts.for_each_mut(|mut text| {
+        text.sections[0].value = format!("Search: {}_\n", filter.query);
+        text.sections[1].value = format!(
+            "Sort: {} (Tab)  Level: {} (F2)\n{} songs  Show: {} (F3)",
+            filter.sort.name(),
+            filter.difficulty.name(),
+            wheel.songs.len(),
+            if filter.uncleared_only {
+                "Not cleared"
+            } else {
+                "All"
+            },
+        );
+    });
+}
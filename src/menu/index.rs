@@ -0,0 +1,99 @@
+use std::collections::{BTreeMap, HashSet};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use super::library::{ChartSummary, SONGS_DIR};
+use crate::types::{chart_hash, SongConfigToml};
+
+/// 谱面索引缓存
+const INDEX_FILE: &str = "cache/library.toml";
+
+/// 一个谱面的缓存
+#[derive(Serialize, Deserialize)]
+struct IndexEntry {
+    /// 修改时间（Unix 时间戳，毫秒）
+    modified: u64,
+    size: u64,
+    /// 谱面内容的哈希，修改时间变了但内容没变时不用重新解析
+    hash: String,
+    summary: ChartSummary,
+}
+
+/// 谱面文件到谱面信息的缓存，修改时间和大小不变时不读取谱面
+#[derive(Default, Serialize, Deserialize)]
+pub(super) struct LibraryIndex {
+    #[serde(default)]
+    charts: BTreeMap<String, IndexEntry>,
+    /// 本次扫描到的谱面，保存时去掉已删除的谱面
+    #[serde(skip)]
+    seen: HashSet<String>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl LibraryIndex {
+    /// 读取缓存，文件不存在或格式错误时为空
+    pub(super) fn load() -> Self {
+        let contents = match std::fs::read_to_string(INDEX_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+        toml::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("couldn't parse {}: {}", INDEX_FILE, e);
+            Self::default()
+        })
+    }
+    /// 有变化时写回缓存
+    pub(super) fn save(&mut self) {
+        let seen = &self.seen;
+        let before = self.charts.len();
+        self.charts
+            .retain(|chart_file, _| seen.contains(chart_file));
+        if !self.changed && self.charts.len() == before {
+            return;
+        }
+        let toml_text = toml::to_string_pretty(self).expect("couldn't convert to toml text");
+        let result =
+            std::fs::create_dir_all("cache").and_then(|_| std::fs::write(INDEX_FILE, toml_text));
+        if let Err(e) = result {
+            eprintln!("couldn't write {}: {}", INDEX_FILE, e);
+        }
+    }
+    /// 谱面内容的哈希和谱面信息：文件没有变化时使用缓存，否则读取并解析
+    pub(super) fn summary(&mut self, chart_file: &str) -> Result<(String, ChartSummary), String> {
+        self.seen.insert(chart_file.to_string());
+        let path = format!("{}/{}", SONGS_DIR, chart_file);
+        let metadata =
+            std::fs::metadata(&path).map_err(|e| format!("couldn't read {}: {}", path, e))?;
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |time| time.as_millis() as u64);
+        let size = metadata.len();
+        if let Some(entry) = self.charts.get(chart_file) {
+            if entry.modified == modified && entry.size == size {
+                return Ok((entry.hash.clone(), entry.summary.clone()));
+            }
+        }
+        let contents =
+            std::fs::read_to_string(&path).map_err(|e| format!("couldn't read {}: {}", path, e))?;
+        let hash = chart_hash(contents.as_bytes());
+        let summary = match self.charts.get(chart_file) {
+            Some(entry) if entry.hash == hash => entry.summary.clone(),
+            _ => ChartSummary::new(&SongConfigToml::parse(chart_file, &contents)?),
+        };
+        self.charts.insert(
+            chart_file.to_string(),
+            IndexEntry {
+                modified,
+                size,
+                hash: hash.clone(),
+                summary: summary.clone(),
+            },
+        );
+        self.changed = true;
+        Ok((hash, summary))
+    }
+}
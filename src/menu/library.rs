@@ -0,0 +1,157 @@
+use std::cmp::Ordering;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::index::LibraryIndex;
+use crate::types::{chart_asset_path, SongConfigToml};
+
+/// 谱面文件所在的文件夹
+pub(super) const SONGS_DIR: &str = "assets/songs";
+
+/// 菜单需要的谱面信息，缓存在索引中，不用每次解析整个谱面
+#[derive(Clone, Serialize, Deserialize)]
+pub(super) struct ChartSummary {
+    pub(super) name: String,
+    /// 音频文件，相对于谱面所在的文件夹
+    pub(super) filename: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) artist: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) difficulty: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) bpm: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) preview_start: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) loudness: Option<f64>,
+    /// 箭头数量
+    pub(super) notes: usize,
+    /// 最后一个箭头的时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) last_click_time: Option<f64>,
+}
+
+impl ChartSummary {
+    pub(super) fn new(config: &SongConfigToml) -> Self {
+        Self {
+            name: config.name.clone(),
+            filename: config.filename.clone(),
+            artist: config.artist.clone(),
+            difficulty: config.difficulty,
+            bpm: config.bpm,
+            preview_start: config.preview_start,
+            loudness: config.loudness,
+            notes: config.arrows.len(),
+            last_click_time: config.last_click_time(),
+        }
+    }
+}
+
+/// 一首可选的歌曲
+pub(super) struct SongEntry {
+    /// 谱面文件（相对于 assets/songs，不含后缀），例如 pack/song/chart
+    pub(super) file: String,
+    /// 所在的歌曲包：assets/songs/<pack>/<song>/<chart>.toml
+    pub(super) pack: Option<String>,
+    /// 谱面内容的哈希，成绩按哈希记录
+    pub(super) hash: String,
+    pub(super) chart: ChartSummary,
+}
+
+impl SongEntry {
+    /// 谱面文件（相对于 assets/songs）
+    pub(super) fn chart_file(&self) -> String {
+        format!("{}.toml", self.file)
+    }
+    /// 音频相对于 assets/songs 的路径
+    pub(super) fn audio_asset(&self) -> String {
+        chart_asset_path(&self.chart_file(), &self.chart.filename)
+    }
+    /// 音频文件路径
+    pub(super) fn audio_path(&self) -> String {
+        format!("{}/{}", SONGS_DIR, self.audio_asset())
+    }
+    /// 按歌曲包和名字排序，不区分大小写
+    pub(super) fn cmp_by_name(&self, other: &Self) -> Ordering {
+        let key = |song: &SongEntry| (song.pack.clone(), song.chart.name.to_lowercase());
+        key(self)
+            .cmp(&key(other))
+            .then_with(|| self.file.cmp(&other.file))
+    }
+}
+
+/// assets/songs 下的所有谱面，包括子文件夹，按歌曲包和名字排序
+#[derive(Default)]
+pub(super) struct SongLibrary {
+    pub(super) songs: Vec<SongEntry>,
+}
+
+impl SongLibrary {
+    /// 重新扫描谱面，没有变化的谱面使用索引缓存，无法解析的谱面跳过
+    pub(super) fn reload(&mut self) {
+        let mut index = LibraryIndex::load();
+        let mut files = Vec::new();
+        find_charts(Path::new(SONGS_DIR), "", &mut files);
+        self.songs = files
+            .into_iter()
+            .filter_map(|file| match index.summary(&format!("{}.toml", file)) {
+                Ok((hash, chart)) => Some(SongEntry {
+                    pack: pack_of(&file),
+                    file,
+                    hash,
+                    chart,
+                }),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    None
+                }
+            })
+            .collect();
+        self.songs.sort_by(SongEntry::cmp_by_name);
+        index.save();
+    }
+}
+
+/// 递归查找谱面文件，files 中是相对于 assets/songs、不含后缀的路径
+fn find_charts(dir: &Path, prefix: &str, files: &mut Vec<String>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("couldn't read {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    let join = |name: &str| {
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", prefix, name)
+        }
+    };
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if path.is_dir() {
+            if let Some(name) = path.file_name().and_then(|name| name.to_str()) {
+                find_charts(&path, &join(name), files);
+            }
+        } else if path.extension().map_or(false, |ext| ext == "toml") {
+            // 去除后缀
+            if let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) {
+                files.push(join(stem));
+            }
+        }
+    }
+}
+
+/// pack/song/chart 中的 pack，直接放在 assets/songs 或歌曲文件夹中的谱面没有歌曲包
+fn pack_of(file: &str) -> Option<String> {
+    let components: Vec<&str> = file.split('/').collect();
+    if components.len() >= 3 {
+        Some(components[0].to_string())
+    } else {
+        None
+    }
+}
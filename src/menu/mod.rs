@@ -0,0 +1,386 @@
+use bevy::prelude::*;
+use bevy::tasks::AsyncComputeTaskPool;
+
+use audio_info::*;
+use filter::*;
+use library::*;
+use options::*;
+use preview::*;
+use settings::*;
+use wheel::*;
+
+use crate::chart::{load_chart, ChartPurpose, SongsChangedEvent};
+use crate::map_maker::MapMakerSession;
+use crate::records::PlayRecords;
+use crate::versus::{VersusChart, VersusSetup};
+use crate::AppState;
+
+mod audio_info;
+mod filter;
+mod index;
+mod library;
+mod options;
+mod preview;
+mod settings;
+mod wheel;
+
+/// 每行按钮的总宽度
+const ROW_WIDTH: f32 = 480.0;
+/// 按钮高度
+const BUTTON_HEIGHT: f32 = 65.0;
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ButtonMaterials>()
+            .init_resource::<SongPreview>()
+            .init_resource::<SongLibrary>()
+            .init_resource::<SongWheel>()
+            .init_resource::<SongFilter>()
+            .init_resource::<AudioInfoCache>()
+            .init_resource::<SettingsCursor>()
+            .add_system_set(SystemSet::on_enter(AppState::Menu).with_system(setup_menu.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Menu)
+                    .with_system(button_interaction.system())
+                    .with_system(reload_library.system())
+                    .with_system(filter_keyboard.system())
+                    .with_system(filter_songs.system())
+                    .with_system(update_filter_text.system())
+                    .with_system(options_keyboard.system())
+                    .with_system(update_options_text.system())
+                    .with_system(wheel_keyboard.system())
+                    .with_system(wheel_mouse_scroll.system())
+                    .with_system(wheel_row_interaction.system())
+                    .with_system(preview_selected_song.system())
+                    .with_system(update_wheel_rows.system())
+                    .with_system(update_wheel_text.system())
+                    .with_system(update_song_info.system())
+                    .with_system(finish_audio_info.system())
+                    .with_system(update_preview.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Menu)
+                    .with_system(despawn_menu.system())
+                    .with_system(stop_preview.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Settings).with_system(setup_settings.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Settings)
+                    .with_system(volume_button_interaction.system())
+                    .with_system(settings_keyboard.system())
+                    .with_system(update_settings_text.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Settings).with_system(despawn_settings.system()),
+            );
+    }
+}
+
+struct ButtonMaterials {
+    none: Handle<ColorMaterial>,
+    normal: Handle<ColorMaterial>,
+    hovered: Handle<ColorMaterial>,
+    pressed: Handle<ColorMaterial>,
+    /// 选中的歌曲
+    selected: Handle<ColorMaterial>,
+    font: Handle<Font>,
+}
+
+impl ButtonMaterials {
+    fn text_style(&self) -> TextStyle {
+        TextStyle {
+            font: self.font.clone(),
+            font_size: 20.0,
+            color: Color::rgb(0.9, 0.9, 0.9),
+        }
+    }
+}
+
+impl FromWorld for ButtonMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        let asset_server = world.get_resource::<AssetServer>().unwrap();
+
+        Self {
+            none: materials.add(Color::NONE.into()),
+            normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
+            hovered: materials.add(Color::rgb(0.35, 0.35, 0.35).into()),
+            pressed: materials.add(Color::rgb(0.55, 0.75, 0.55).into()),
+            selected: materials.add(Color::rgb(0.25, 0.45, 0.3).into()),
+            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+        }
+    }
+}
+
+/// 歌曲列表下方的按钮
+enum MenuButton {
+    MakeMap,
+    /// 在选中的谱面上叠加录制
+    Overdub,
+    /// 练习选中的谱面
+    Practice,
+    /// 双人对战选中的歌曲
+    Versus,
+    Settings,
+}
+
+impl MenuButton {
+    const ALL: [MenuButton; 5] = [
+        MenuButton::MakeMap,
+        MenuButton::Overdub,
+        MenuButton::Practice,
+        MenuButton::Versus,
+        MenuButton::Settings,
+    ];
+    fn name(&self) -> &'static str {
+        match self {
+            MenuButton::MakeMap => "New chart",
+            MenuButton::Overdub => "Overdub",
+            MenuButton::Practice => "Practice",
+            MenuButton::Versus => "Versus",
+            MenuButton::Settings => "Settings",
+        }
+    }
+}
+
+struct MenuUI;
+
+/// 初始菜单：左边是歌曲列表和按钮，右边是选中歌曲的信息
+fn setup_menu(
+    mut cmd: Commands,
+    button_materials: Res<ButtonMaterials>,
+    mut library: ResMut<SongLibrary>,
+    mut wheel: ResMut<SongWheel>,
+    filter: Res<SongFilter>,
+    records: Res<PlayRecords>,
+    audio_info: Res<AudioInfoCache>,
+) {
+    // 重新读取谱面，保持上次选中的歌曲；同时标记变化，让预览和面板刷新
+    let keep = wheel.selected_song(&library).map(|song| song.file.clone());
+    library.reload();
+    wheel.refresh(&library, &filter, &records, &audio_info, keep.as_deref());
+    // node > column > row > button > text
+    let node_bundle = NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::Row,
+            justify_content: JustifyContent::SpaceAround,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: button_materials.none.clone(),
+        ..Default::default()
+    };
+    let column_bundle = NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::ColumnReverse,
+            ..Default::default()
+        },
+        material: button_materials.none.clone(),
+        ..Default::default()
+    };
+    let row_bundle = NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(ROW_WIDTH), Val::Px(BUTTON_HEIGHT)),
+            margin: Rect {
+                top: Val::Px(10.0),
+                ..Default::default()
+            },
+            flex_direction: FlexDirection::Row,
+            justify_content: JustifyContent::SpaceBetween,
+            ..Default::default()
+        },
+        material: button_materials.none.clone(),
+        ..Default::default()
+    };
+    cmd.spawn_bundle(node_bundle)
+        .insert(MenuUI)
+        .with_children(|parent_node| {
+            parent_node
+                .spawn_bundle(column_bundle)
+                .with_children(|parent_column| {
+                    spawn_filter_bar(parent_column, &button_materials);
+                    spawn_wheel(parent_column, &button_materials);
+                    spawn_options_bar(parent_column, &button_materials);
+                    parent_column
+                        .spawn_bundle(row_bundle)
+                        .with_children(|parent_row| {
+                            for menu_button in MenuButton::ALL {
+                                spawn_button(parent_row, &button_materials, menu_button);
+                            }
+                        });
+                });
+            spawn_info_panel(parent_node, &button_materials);
+        });
+}
+
+/// 歌曲文件夹变化时重新读取谱面
+fn reload_library(
+    mut changed_event: EventReader<SongsChangedEvent>,
+    mut library: ResMut<SongLibrary>,
+    mut wheel: ResMut<SongWheel>,
+    filter: Res<SongFilter>,
+    records: Res<PlayRecords>,
+    audio_info: Res<AudioInfoCache>,
+) {
+    if changed_event.iter().next().is_none() {
+        return;
+    }
+    let keep = wheel.selected_song(&library).map(|song| song.file.clone());
+    library.reload();
+    wheel.refresh(&library, &filter, &records, &audio_info, keep.as_deref());
+}
+
+fn spawn_button(
+    parent_row: &mut ChildBuilder,
+    button_materials: &ButtonMaterials,
+    menu_button: MenuButton,
+) {
+    let width = (ROW_WIDTH - 20.0) / MenuButton::ALL.len() as f32;
+    let button_bundle = ButtonBundle {
+        style: Style {
+            size: Size::new(Val::Px(width), Val::Px(BUTTON_HEIGHT)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: button_materials.normal.clone(),
+        ..Default::default()
+    };
+    parent_row
+        .spawn_bundle(button_bundle)
+        .with_children(|parent_button| {
+            let text_bundle = TextBundle {
+                text: Text::with_section(
+                    menu_button.name(),
+                    button_materials.text_style(),
+                    TextAlignment::default(),
+                ),
+                ..Default::default()
+            };
+            parent_button.spawn_bundle(text_bundle);
+        })
+        .insert(menu_button);
+}
+
+/// 移除菜单和按钮
+fn despawn_menu(mut cmd: Commands, menu: Query<Entity, With<MenuUI>>) {
+    menu.for_each(|e| cmd.entity(e).despawn_recursive());
+}
+
+type InteractionButton = (Changed<Interaction>, With<Button>);
+
+/// 按钮样式变换
+fn button_interaction(
+    mut cmd: Commands,
+    mut state: ResMut<State<AppState>>,
+    button_materials: Res<ButtonMaterials>,
+    interaction_button: Query<
+        (&Interaction, &mut Handle<ColorMaterial>, &MenuButton),
+        InteractionButton,
+    >,
+    wheel: Res<SongWheel>,
+    library: Res<SongLibrary>,
+    asset_server: Res<AssetServer>,
+) {
+    interaction_button.for_each_mut(
+        |(interaction, mut material, menu_button)| match interaction {
+            Interaction::Clicked => {
+                *material = button_materials.pressed.clone();
+                let song = wheel.selected_song(&library);
+                button_pressed_handle(
+                    &mut cmd,
+                    &mut state,
+                    menu_button,
+                    &library,
+                    song,
+                    &asset_server,
+                );
+            }
+            Interaction::Hovered => *material = button_materials.hovered.clone(),
+            Interaction::None => *material = button_materials.normal.clone(),
+        },
+    );
+}
+
+fn button_pressed_handle(
+    cmd: &mut Commands,
+    state: &mut State<AppState>,
+    menu_button: &MenuButton,
+    library: &SongLibrary,
+    selected: Option<&SongEntry>,
+    asset_server: &AssetServer,
+) {
+    match menu_button {
+        MenuButton::MakeMap => {
+            cmd.insert_resource(MapMakerSession::New);
+            state.set(AppState::MakeMap).unwrap();
+        }
+        MenuButton::Settings => state.set(AppState::Settings).unwrap(),
+        MenuButton::Overdub => {
+            if let Some(song) = selected {
+                let chart_file = song.chart_file();
+                load_chart(cmd, state, asset_server, chart_file, ChartPurpose::Overdub);
+            }
+        }
+        MenuButton::Practice => {
+            if let Some(song) = selected {
+                let chart_file = song.chart_file();
+                load_chart(cmd, state, asset_server, chart_file, ChartPurpose::Practice);
+            }
+        }
+        MenuButton::Versus => {
+            if let Some(song) = selected {
+                cmd.insert_resource(versus_setup(library, song));
+                state.set(AppState::Versus).unwrap();
+            }
+        }
+    }
+}
+
+/// 使用同一个音频的谱面都可以用来对战，按难度排序，两个玩家从选中的谱面开始
+fn versus_setup(library: &SongLibrary, selected: &SongEntry) -> VersusSetup {
+    let audio = selected.audio_asset();
+    let mut songs: Vec<&SongEntry> = library
+        .songs
+        .iter()
+        .filter(|song| song.audio_asset() == audio)
+        .collect();
+    songs.sort_by_key(|song| song.chart.difficulty);
+    let index = songs
+        .iter()
+        .position(|song| song.file == selected.file)
+        .unwrap_or(0);
+    let charts = songs
+        .iter()
+        .map(|song| VersusChart {
+            chart_file: song.chart_file(),
+            label: match song.chart.difficulty {
+                Some(level) => format!("{} [Lv. {}]", song.chart.name, level),
+                None => song.chart.name.clone(),
+            },
+        })
+        .collect();
+    VersusSetup::new(charts, index)
+}
+
+/// 开始游戏
+fn play_song(
+    cmd: &mut Commands,
+    state: &mut State<AppState>,
+    song: &SongEntry,
+    asset_server: &AssetServer,
+) {
+    load_chart(
+        cmd,
+        state,
+        asset_server,
+        song.chart_file(),
+        ChartPurpose::Play,
+    );
+}
@@ -0,0 +1,85 @@
+use super::*;
+use crate::gauge::GaugeType;
+use crate::mods::{LaneMod, VisibilityMod, HI_SPEED_STEP, LANE_COVER_STEP};
+use crate::settings::Settings;
+
+/// 游戏选项的文字
+pub(super) struct OptionsText;
+
+pub(super) fn spawn_options_bar(parent: &mut ChildBuilder, button_materials: &ButtonMaterials) {
+    parent
+        .spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect {
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font_size: 16.0,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                    ..button_materials.text_style()
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(OptionsText);
+}
+
+/// F4 切换血条类型，F5 切换方向 mod，F6/F7 调整高速，F8 切换遮挡 mod，F9/F10 调整遮挡比例
+pub(super) fn options_keyboard(key_input: Res<Input<KeyCode>>, mut settings: ResMut<Settings>) {
+    let gameplay = &mut settings.gameplay;
+    if key_input.just_pressed(KeyCode::F4) {
+        gameplay.gauge = next(&GaugeType::ALL, gameplay.gauge);
+    }
+    if key_input.just_pressed(KeyCode::F5) {
+        gameplay.mods.lane = next(&LaneMod::ALL, gameplay.mods.lane);
+    }
+    if key_input.just_pressed(KeyCode::F6) {
+        gameplay.mods.adjust_hi_speed(-HI_SPEED_STEP);
+    }
+    if key_input.just_pressed(KeyCode::F7) {
+        gameplay.mods.adjust_hi_speed(HI_SPEED_STEP);
+    }
+    if key_input.just_pressed(KeyCode::F8) {
+        gameplay.mods.visibility = next(&VisibilityMod::ALL, gameplay.mods.visibility);
+    }
+    if key_input.just_pressed(KeyCode::F9) {
+        gameplay.mods.adjust_lane_cover(-LANE_COVER_STEP);
+    }
+    if key_input.just_pressed(KeyCode::F10) {
+        gameplay.mods.adjust_lane_cover(LANE_COVER_STEP);
+    }
+}
+
+pub(super) fn update_options_text(
+    settings: Res<Settings>,
+    added: Query<(), Added<OptionsText>>,
+    texts: Query<&mut Text, With<OptionsText>>,
+) {
+    if !(settings.is_changed() || added.iter().next().is_some()) {
+        return;
+    }
+    let gameplay = &settings.gameplay;
+    let ranked = if gameplay.mods.ranked() {
+        ""
+    } else {
+        " (unranked)"
+    };
+    texts.for_each_mut(|mut text| {
+        text.sections[0].value = format!(
+            "Gauge: {} (F4)  Lane: {} (F5){}  Hi-speed: x{:.2} (F6/F7)\n\
+             Visibility: {} (F8)  Lane cover: {:.0}% (F9/F10)",
+            gameplay.gauge.name(),
+            gameplay.mods.lane.name(),
+            ranked,
+            gameplay.mods.hi_speed,
+            gameplay.mods.visibility.name(),
+            gameplay.mods.lane_cover * 100.0
+        );
+    });
+}
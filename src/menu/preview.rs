@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use bevy::tasks::AsyncComputeTaskPool;
+
+use super::audio_info::AudioInfoCache;
+use super::library::SongEntry;
+use crate::audio::{normalization_gain, AudioChannel, AudioPlayer, SoundHandle};
+
+/// 预览片段长度（秒）
+pub(super) const PREVIEW_LENGTH: f64 = 15.0;
+/// 淡入淡出时间（秒）
+const PREVIEW_FADE: f64 = 0.5;
+
+/// 正在播放的预览
+struct Preview {
+    song: String,
+    audio_path: String,
+    audio: Handle<AudioSource>,
+    /// 片段开始位置，还在计算最响片段时为 None
+    start: Option<f64>,
+    sound: Option<SoundHandle>,
+    /// 响度统一的增益
+    gain: f32,
+    volume: f32,
+}
+
+/// 切换歌曲后正在淡出的预览
+struct FadingPreview {
+    sound: SoundHandle,
+    volume: f32,
+}
+
+/// 菜单中的歌曲预览，循环播放一个片段，切换时淡入淡出
+#[derive(Default)]
+pub(super) struct SongPreview {
+    current: Option<Preview>,
+    fading: Vec<FadingPreview>,
+}
+
+impl SongPreview {
+    /// 选中歌曲，切换到它的预览
+    pub(super) fn select(&mut self, song: &SongEntry, asset_server: &AssetServer) {
+        if self.current.as_ref().map_or(false, |p| p.song == song.file) {
+            return;
+        }
+        self.fade_out();
+        let chart = &song.chart;
+        self.current = Some(Preview {
+            song: song.file.clone(),
+            audio_path: song.audio_path(),
+            audio: asset_server.load(&*format!("songs/{}", song.audio_asset())),
+            start: chart.preview_start,
+            sound: None,
+            gain: normalization_gain(chart.loudness),
+            volume: 0.0,
+        });
+    }
+    /// 淡出当前预览
+    fn fade_out(&mut self) {
+        if let Some(preview) = self.current.take() {
+            if let Some(sound) = preview.sound {
+                self.fading.push(FadingPreview {
+                    sound,
+                    volume: preview.volume,
+                });
+            }
+        }
+    }
+    /// 立即停止所有预览
+    fn stop(&mut self, player: &mut AudioPlayer) {
+        self.fade_out();
+        self.fading
+            .drain(..)
+            .for_each(|fading| player.stop(fading.sound));
+    }
+}
+
+/// 片段开头淡入，结尾淡出
+fn envelope(offset: f64) -> f32 {
+    let fade_in = offset / PREVIEW_FADE;
+    let fade_out = (PREVIEW_LENGTH - offset) / PREVIEW_FADE;
+    fade_in.min(fade_out).max(0.0).min(1.0) as f32
+}
+
+pub(super) fn update_preview(
+    mut preview: ResMut<SongPreview>,
+    mut player: ResMut<AudioPlayer>,
+    mut audio_info: ResMut<AudioInfoCache>,
+    time: Res<Time>,
+    pool: Res<AsyncComputeTaskPool>,
+) {
+    let step = (time.delta_seconds_f64() / PREVIEW_FADE) as f32;
+    preview.fading.retain(|fading| fading.volume > 0.0);
+    for fading in preview.fading.iter_mut() {
+        fading.volume -= step;
+        if fading.volume > 0.0 {
+            player.set_volume(fading.sound, fading.volume);
+        } else {
+            player.stop(fading.sound);
+        }
+    }
+    let current = match preview.current.as_mut() {
+        Some(current) => current,
+        None => return,
+    };
+    let start = match current.start {
+        Some(start) => start,
+        None => {
+            // 谱面没有 preview_start 时使用最响的片段
+            let info = audio_info.get(&current.audio_path, &pool);
+            if audio_info.is_pending(&current.audio_path) {
+                return;
+            }
+            *current.start.insert(info.map_or(0.0, |info| info.loudest))
+        }
+    };
+    let position = current.sound.and_then(|sound| player.position(sound));
+    let sound = match (current.sound, position) {
+        (Some(sound), Some(position)) if position >= start + PREVIEW_LENGTH => {
+            // 回到片段开头循环播放
+            player.seek(sound, start);
+            sound
+        }
+        (Some(sound), Some(_)) => sound,
+        // 还没开始，或者歌曲比片段短已经播放完
+        _ => {
+            let sound = player.play_from(current.audio.clone(), AudioChannel::Preview, start);
+            current.sound = Some(sound);
+            sound
+        }
+    };
+    let offset = player.position(sound).unwrap_or(start) - start;
+    current.volume = envelope(offset) * current.gain;
+    player.set_volume(sound, current.volume);
+}
+
+/// 离开菜单时停止预览
+pub(super) fn stop_preview(mut preview: ResMut<SongPreview>, mut player: ResMut<AudioPlayer>) {
+    preview.stop(&mut player);
+}
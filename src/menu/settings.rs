@@ -0,0 +1,218 @@
+use super::*;
+use crate::settings::{Settings, VolumeSettings};
+
+/// 每次调整的音量
+const VOLUME_STEP: f32 = 0.05;
+
+/// 音量滑块
+#[derive(Copy, Clone, PartialEq)]
+pub(super) enum VolumeSlider {
+    Master,
+    Music,
+    Effects,
+    Ui,
+}
+
+impl VolumeSlider {
+    const ALL: [VolumeSlider; 4] = [
+        VolumeSlider::Master,
+        VolumeSlider::Music,
+        VolumeSlider::Effects,
+        VolumeSlider::Ui,
+    ];
+    fn name(&self) -> &'static str {
+        match self {
+            VolumeSlider::Master => "Master",
+            VolumeSlider::Music => "Music",
+            VolumeSlider::Effects => "Hit sounds",
+            VolumeSlider::Ui => "UI",
+        }
+    }
+    fn value(&self, volume: &VolumeSettings) -> f32 {
+        match self {
+            VolumeSlider::Master => volume.master,
+            VolumeSlider::Music => volume.music,
+            VolumeSlider::Effects => volume.effects,
+            VolumeSlider::Ui => volume.ui,
+        }
+    }
+    fn value_mut<'a>(&self, volume: &'a mut VolumeSettings) -> &'a mut f32 {
+        match self {
+            VolumeSlider::Master => &mut volume.master,
+            VolumeSlider::Music => &mut volume.music,
+            VolumeSlider::Effects => &mut volume.effects,
+            VolumeSlider::Ui => &mut volume.ui,
+        }
+    }
+    /// 调整音量，保持在 0 到 1
+    fn adjust(&self, volume: &mut VolumeSettings, delta: f32) {
+        let value = self.value_mut(volume);
+        *value = ((*value + delta).max(0.0).min(1.0) * 100.0).round() / 100.0;
+    }
+}
+
+/// 键盘选中的滑块
+#[derive(Default)]
+pub(super) struct SettingsCursor(usize);
+
+pub(super) struct SettingsUI;
+/// 滑块的文字
+pub(super) struct VolumeText(VolumeSlider);
+/// 滑块的 -/+ 按钮
+pub(super) struct VolumeButton(VolumeSlider, f32);
+
+/// 设置界面
+pub(super) fn setup_settings(mut cmd: Commands, button_materials: Res<ButtonMaterials>) {
+    let node_bundle = NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: button_materials.none.clone(),
+        ..Default::default()
+    };
+    let text_style = TextStyle {
+        font: button_materials.font.clone(),
+        font_size: 20.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    cmd.spawn_bundle(node_bundle)
+        .insert(SettingsUI)
+        .with_children(|parent_node| {
+            for slider in VolumeSlider::ALL.iter() {
+                let row_bundle = NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(ROW_WIDTH), Val::Px(BUTTON_HEIGHT)),
+                        margin: Rect::all(Val::Px(5.0)),
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    material: button_materials.none.clone(),
+                    ..Default::default()
+                };
+                parent_node
+                    .spawn_bundle(row_bundle)
+                    .with_children(|parent_row| {
+                        parent_row
+                            .spawn_bundle(TextBundle {
+                                style: Style {
+                                    margin: Rect {
+                                        right: Val::Auto,
+                                        ..Default::default()
+                                    },
+                                    ..Default::default()
+                                },
+                                text: Text::with_section(
+                                    "",
+                                    text_style.clone(),
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            })
+                            .insert(VolumeText(*slider));
+                        for (label, delta) in [("-", -VOLUME_STEP), ("+", VOLUME_STEP)].iter() {
+                            parent_row
+                                .spawn_bundle(ButtonBundle {
+                                    style: Style {
+                                        size: Size::new(
+                                            Val::Px(BUTTON_HEIGHT),
+                                            Val::Px(BUTTON_HEIGHT),
+                                        ),
+                                        margin: Rect {
+                                            left: Val::Px(10.0),
+                                            ..Default::default()
+                                        },
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..Default::default()
+                                    },
+                                    material: button_materials.normal.clone(),
+                                    ..Default::default()
+                                })
+                                .with_children(|parent_button| {
+                                    parent_button.spawn_bundle(TextBundle {
+                                        text: Text::with_section(
+                                            *label,
+                                            text_style.clone(),
+                                            Default::default(),
+                                        ),
+                                        ..Default::default()
+                                    });
+                                })
+                                .insert(VolumeButton(*slider, *delta));
+                        }
+                    });
+            }
+        });
+}
+
+/// 点击 -/+ 调整音量
+pub(super) fn volume_button_interaction(
+    button_materials: Res<ButtonMaterials>,
+    mut settings: ResMut<Settings>,
+    interaction_button: Query<
+        (&Interaction, &mut Handle<ColorMaterial>, &VolumeButton),
+        InteractionButton,
+    >,
+) {
+    interaction_button.for_each_mut(|(interaction, mut material, button)| match interaction {
+        Interaction::Clicked => {
+            *material = button_materials.pressed.clone();
+            button.0.adjust(&mut settings.volume, button.1);
+        }
+        Interaction::Hovered => *material = button_materials.hovered.clone(),
+        Interaction::None => *material = button_materials.normal.clone(),
+    });
+}
+
+/// 上下选择滑块，左右调整音量
+pub(super) fn settings_keyboard(
+    key_input: Res<Input<KeyCode>>,
+    mut cursor: ResMut<SettingsCursor>,
+    mut settings: ResMut<Settings>,
+) {
+    let len = VolumeSlider::ALL.len();
+    if key_input.just_pressed(KeyCode::Up) {
+        cursor.0 = (cursor.0 + len - 1) % len;
+    }
+    if key_input.just_pressed(KeyCode::Down) {
+        cursor.0 = (cursor.0 + 1) % len;
+    }
+    let slider = VolumeSlider::ALL[cursor.0];
+    if key_input.just_pressed(KeyCode::Left) {
+        slider.adjust(&mut settings.volume, -VOLUME_STEP);
+    }
+    if key_input.just_pressed(KeyCode::Right) {
+        slider.adjust(&mut settings.volume, VOLUME_STEP);
+    }
+}
+
+/// 更新滑块文字，选中的滑块高亮
+pub(super) fn update_settings_text(
+    settings: Res<Settings>,
+    cursor: Res<SettingsCursor>,
+    added: Query<(), Added<VolumeText>>,
+    q: Query<(&mut Text, &VolumeText)>,
+) {
+    if !(settings.is_changed() || cursor.is_changed() || added.iter().next().is_some()) {
+        return;
+    }
+    q.for_each_mut(|(mut text, volume_text)| {
+        let slider = volume_text.0;
+        let selected = VolumeSlider::ALL[cursor.0] == slider;
+        let value = slider.value(&settings.volume);
+        text.sections[0].value = format!("{}: {:.0}%", slider.name(), value * 100.0);
+        text.sections[0].style.color = if selected {
+            Color::rgb(0.55, 0.75, 0.55)
+        } else {
+            Color::rgb(0.9, 0.9, 0.9)
+        };
+    });
+}
+
+pub(super) fn despawn_settings(mut cmd: Commands, q: Query<Entity, With<SettingsUI>>) {
+    q.for_each(|e| cmd.entity(e).despawn_recursive());
+}
@@ -0,0 +1,365 @@
+use bevy::input::mouse::MouseWheel;
+
+use super::*;
+use crate::records::{ModsRecord, PlayRecords, ScoreRecords};
+
+/// 同时显示的歌曲行数
+const VISIBLE_ROWS: usize = 7;
+/// 歌曲行高度
+const WHEEL_ROW_HEIGHT: f32 = 50.0;
+/// 信息面板宽度
+const PANEL_WIDTH: f32 = 350.0;
+
+/// 歌曲列表和选中的歌曲，返回菜单时保留
+#[derive(Default)]
+pub(super) struct SongWheel {
+    /// 搜索、过滤和排序后的歌曲（SongLibrary 中的下标）
+    pub(super) songs: Vec<usize>,
+    /// 选中的是 songs 中的第几首
+    selected: usize,
+}
+
+impl SongWheel {
+    /// 重新生成列表，尽量保持选中名为 keep 的谱面
+    pub(super) fn refresh(
+        &mut self,
+        library: &SongLibrary,
+        filter: &SongFilter,
+        records: &PlayRecords,
+        audio_info: &AudioInfoCache,
+        keep: Option<&str>,
+    ) {
+        let entries = &library.songs;
+        let mut songs: Vec<usize> = (0..entries.len())
+            .filter(|&i| filter.matches(&entries[i], records))
+            .collect();
+        songs.sort_by(|&a, &b| filter.compare(&entries[a], &entries[b], records, audio_info));
+        self.selected = keep
+            .and_then(|keep| songs.iter().position(|&i| entries[i].file == keep))
+            .unwrap_or(0);
+        self.songs = songs;
+    }
+    pub(super) fn selected_song<'a>(&self, library: &'a SongLibrary) -> Option<&'a SongEntry> {
+        self.songs.get(self.selected).map(|&i| &library.songs[i])
+    }
+    /// 移动选择，到头后从另一端继续
+    fn step(&mut self, delta: isize, len: usize) {
+        if len > 0 {
+            self.selected = (self.selected as isize + delta).rem_euclid(len as isize) as usize;
+        }
+    }
+    /// 移动选择，停在两端
+    fn scroll(&mut self, delta: isize, len: usize) {
+        let last = len.saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).max(0).min(last) as usize;
+    }
+    /// 显示的第一行对应的歌曲，让选中的歌曲尽量在中间
+    fn first_visible(&self, len: usize) -> usize {
+        self.selected
+            .saturating_sub(VISIBLE_ROWS / 2)
+            .min(len.saturating_sub(VISIBLE_ROWS))
+    }
+    /// 显示在第 row 行的是 songs 中的第几首
+    fn song_at_row(&self, row: usize) -> Option<usize> {
+        Some(self.first_visible(self.songs.len()) + row).filter(|&i| i < self.songs.len())
+    }
+}
+
+/// 歌曲行按钮，值为第几行
+pub(super) struct WheelRow(usize);
+/// 歌曲行的文字
+pub(super) struct WheelRowText(usize);
+/// 信息面板的文字
+pub(super) struct SongInfoText;
+
+/// 歌曲列表
+pub(super) fn spawn_wheel(parent: &mut ChildBuilder, button_materials: &ButtonMaterials) {
+    for row in 0..VISIBLE_ROWS {
+        let button_bundle = ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Px(ROW_WIDTH), Val::Px(WHEEL_ROW_HEIGHT)),
+                margin: Rect {
+                    bottom: Val::Px(5.0),
+                    ..Default::default()
+                },
+                padding: Rect {
+                    left: Val::Px(15.0),
+                    ..Default::default()
+                },
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: button_materials.normal.clone(),
+            ..Default::default()
+        };
+        parent
+            .spawn_bundle(button_bundle)
+            .with_children(|parent_button| {
+                parent_button
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "",
+                            button_materials.text_style(),
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(WheelRowText(row));
+            })
+            .insert(WheelRow(row));
+    }
+}
+
+/// 选中歌曲的信息面板
+pub(super) fn spawn_info_panel(parent: &mut ChildBuilder, button_materials: &ButtonMaterials) {
+    let panel_bundle = NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(PANEL_WIDTH), Val::Percent(100.0)),
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            padding: Rect::all(Val::Px(20.0)),
+            ..Default::default()
+        },
+        material: button_materials.none.clone(),
+        ..Default::default()
+    };
+    parent
+        .spawn_bundle(panel_bundle)
+        .with_children(|parent_panel| {
+            parent_panel
+                .spawn_bundle(TextBundle {
+                    style: Style {
+                        max_size: Size::new(Val::Px(PANEL_WIDTH - 40.0), Val::Undefined),
+                        ..Default::default()
+                    },
+                    text: Text::with_section("", button_materials.text_style(), Default::default()),
+                    ..Default::default()
+                })
+                .insert(SongInfoText);
+            parent_panel.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        top: Val::Px(30.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "Type to search  Up/Down: select  Enter: play",
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::rgb(0.6, 0.6, 0.6),
+                        ..button_materials.text_style()
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+        });
+}
+
+/// 上下键移动选择，PageUp / PageDown 翻页，回车开始游戏
+pub(super) fn wheel_keyboard(
+    mut cmd: Commands,
+    mut state: ResMut<State<AppState>>,
+    mut wheel: ResMut<SongWheel>,
+    key_input: Res<Input<KeyCode>>,
+    library: Res<SongLibrary>,
+    asset_server: Res<AssetServer>,
+) {
+    let len = wheel.songs.len();
+    if key_input.just_pressed(KeyCode::Up) {
+        wheel.step(-1, len);
+    }
+    if key_input.just_pressed(KeyCode::Down) {
+        wheel.step(1, len);
+    }
+    if key_input.just_pressed(KeyCode::PageUp) {
+        wheel.scroll(-(VISIBLE_ROWS as isize), len);
+    }
+    if key_input.just_pressed(KeyCode::PageDown) {
+        wheel.scroll(VISIBLE_ROWS as isize, len);
+    }
+    if key_input.just_pressed(KeyCode::Return) {
+        if let Some(song) = wheel.selected_song(&library) {
+            play_song(&mut cmd, &mut state, song, &asset_server);
+        }
+    }
+}
+
+/// 鼠标滚轮移动选择
+pub(super) fn wheel_mouse_scroll(
+    mut scroll: EventReader<MouseWheel>,
+    mut wheel: ResMut<SongWheel>,
+) {
+    let len = wheel.songs.len();
+    for event in scroll.iter() {
+        if event.y > 0.0 {
+            wheel.scroll(-1, len);
+        } else if event.y < 0.0 {
+            wheel.scroll(1, len);
+        }
+    }
+}
+
+/// 点击歌曲行开始游戏，鼠标移上去时预览
+pub(super) fn wheel_row_interaction(
+    mut cmd: Commands,
+    mut state: ResMut<State<AppState>>,
+    mut preview: ResMut<SongPreview>,
+    wheel: Res<SongWheel>,
+    library: Res<SongLibrary>,
+    asset_server: Res<AssetServer>,
+    interaction_row: Query<(&Interaction, &WheelRow), InteractionButton>,
+) {
+    interaction_row.for_each(|(interaction, row)| {
+        let song = match wheel.song_at_row(row.0) {
+            Some(i) => &library.songs[wheel.songs[i]],
+            None => return,
+        };
+        match interaction {
+            Interaction::Clicked => play_song(&mut cmd, &mut state, song, &asset_server),
+            Interaction::Hovered => preview.select(song, &asset_server),
+            Interaction::None => {}
+        }
+    });
+}
+
+/// 选择变化时预览选中的歌曲
+pub(super) fn preview_selected_song(
+    mut preview: ResMut<SongPreview>,
+    wheel: Res<SongWheel>,
+    library: Res<SongLibrary>,
+    asset_server: Res<AssetServer>,
+) {
+    if !wheel.is_changed() {
+        return;
+    }
+    if let Some(song) = wheel.selected_song(&library) {
+        preview.select(song, &asset_server);
+    }
+}
+
+/// 歌曲行的颜色：选中的高亮，鼠标所在的变亮，没有歌曲的隐藏
+pub(super) fn update_wheel_rows(
+    button_materials: Res<ButtonMaterials>,
+    wheel: Res<SongWheel>,
+    rows: Query<(&Interaction, &mut Handle<ColorMaterial>, &WheelRow)>,
+) {
+    rows.for_each_mut(|(interaction, mut material, row)| {
+        let wanted = match (wheel.song_at_row(row.0), interaction) {
+            (None, _) => &button_materials.none,
+            (Some(_), Interaction::Clicked) => &button_materials.pressed,
+            (Some(i), _) if i == wheel.selected => &button_materials.selected,
+            (Some(_), Interaction::Hovered) => &button_materials.hovered,
+            (Some(_), Interaction::None) => &button_materials.normal,
+        };
+        // 只在颜色变化时修改，避免每帧触发变化检测
+        if *material != *wanted {
+            *material = wanted.clone();
+        }
+    });
+}
+
+/// 选择或歌曲列表变化时更新歌曲行的文字
+pub(super) fn update_wheel_text(
+    wheel: Res<SongWheel>,
+    library: Res<SongLibrary>,
+    added: Query<(), Added<WheelRowText>>,
+    texts: Query<(&mut Text, &WheelRowText)>,
+) {
+    if !(wheel.is_changed() || library.is_changed() || added.iter().next().is_some()) {
+        return;
+    }
+    texts.for_each_mut(|(mut text, row)| {
+        text.sections[0].value = wheel.song_at_row(row.0).map_or_else(String::new, |i| {
+            library.songs[wheel.songs[i]].chart.name.clone()
+        });
+    });
+}
+
+/// 更新信息面板：名字、作者、BPM、长度、箭头数、难度和最高分
+pub(super) fn update_song_info(
+    wheel: Res<SongWheel>,
+    library: Res<SongLibrary>,
+    mut audio_info: ResMut<AudioInfoCache>,
+    pool: Res<AsyncComputeTaskPool>,
+    mut waiting: Local<bool>,
+    scores: Res<ScoreRecords>,
+    mut texts: Query<(&mut Text, ChangeTrackers<SongInfoText>)>,
+) {
+    let song = wheel.selected_song(&library);
+    let pending = song.map_or(false, |song| audio_info.is_pending(&song.audio_path()));
+    // 选择变化，或者选中歌曲的长度刚计算完
+    let finished = *waiting && !pending;
+    let added = texts.iter_mut().any(|(_, tracker)| tracker.is_added());
+    if !(wheel.is_changed() || library.is_changed() || finished || added) {
+        return;
+    }
+    let value = match song {
+        Some(song) => {
+            let chart = &song.chart;
+            let info = audio_info.get(&song.audio_path(), &pool);
+            *waiting = audio_info.is_pending(&song.audio_path());
+            let length = match info {
+                Some(info) => format_length(info.length),
+                None if *waiting => String::from("..."),
+                // 音频无法解码时用最后一个箭头估计
+                None => chart
+                    .last_click_time
+                    .map_or_else(|| "-".into(), format_length),
+            };
+            let pack = song
+                .pack
+                .as_ref()
+                .map_or_else(String::new, |pack| format!("Pack: {}\n", pack));
+            let best = match scores.get(&song.hash) {
+                Some(best) => match (best.best_grade, best.lamp) {
+                    (Some(grade), Some(lamp)) => format!(
+                        "{:07} ({}, {:.2}%)\n{}\nMods: {}\nPlays: {}",
+                        best.best_score,
+                        grade.name(),
+                        best.best_accuracy,
+                        lamp.name(),
+                        best.best_mods
+                            .as_ref()
+                            .map_or_else(|| "-".into(), ModsRecord::name),
+                        best.play_count
+                    ),
+                    _ => format!("-\nPlays: {}", best.play_count),
+                },
+                None => String::from("-"),
+            };
+            format!(
+                "{}\n{}\n\n{}BPM: {}\nLength: {}\nNotes: {}\nDifficulty: {}\nBest score: {}",
+                chart.name,
+                chart.artist.as_deref().unwrap_or("Unknown artist"),
+                pack,
+                chart
+                    .bpm
+                    .map_or_else(|| "-".into(), |bpm| format!("{:.0}", bpm)),
+                length,
+                chart.notes,
+                chart
+                    .difficulty
+                    .map_or_else(|| "-".into(), |level| format!("Lv. {}", level)),
+                best,
+            )
+        }
+        None => {
+            *waiting = false;
+            if library.songs.is_empty() {
+                String::from("No songs in assets/songs")
+            } else {
+                String::from("No songs match the search")
+            }
+        }
+    };
+    texts.for_each_mut(|(mut text, _)| text.sections[0].value = value.clone());
+}
+
+/// 秒数显示为 m:ss
+fn format_length(seconds: f64) -> String {
+    let seconds = seconds.max(0.0).round() as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
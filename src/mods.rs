@@ -0,0 +1,370 @@
+use serde::{Deserialize, Serialize};
+
+use crate::types::{ArrowTime, Directions};
+
+/// 高速的范围和每次调整的大小
+const MIN_HI_SPEED: f32 = 0.5;
+const MAX_HI_SPEED: f32 = 3.0;
+pub const HI_SPEED_STEP: f32 = 0.25;
+/// 每次调整的遮挡比例
+pub const LANE_COVER_STEP: f32 = 0.05;
+/// 箭头渐隐/渐显经过的距离占跑道的比例
+const FADE_LENGTH: f32 = 0.1;
+/// 点击时间相差不到这么多（秒）的箭头算同时按下
+const CHORD_WINDOW: f64 = 0.001;
+
+/// 改变箭头方向的 mod，同时只能选一个
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LaneMod {
+    Off,
+    /// 交换左右
+    Mirror,
+    /// 整首歌使用同一个随机的方向排列
+    Random,
+    /// 每个箭头随机方向，尽量不连续出现在同一方向
+    Shuffle,
+}
+
+impl Default for LaneMod {
+    fn default() -> Self {
+        LaneMod::Off
+    }
+}
+
+impl LaneMod {
+    pub const ALL: [LaneMod; 4] = [
+        LaneMod::Off,
+        LaneMod::Mirror,
+        LaneMod::Random,
+        LaneMod::Shuffle,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            LaneMod::Off => "Off",
+            LaneMod::Mirror => "Mirror",
+            LaneMod::Random => "Random",
+            LaneMod::Shuffle => "Shuffle",
+        }
+    }
+    /// 随机的谱面每次都不一样，成绩不计入排名
+    fn ranked(&self) -> bool {
+        matches!(self, LaneMod::Off | LaneMod::Mirror)
+    }
+}
+
+/// 遮挡箭头的 mod，Hidden 和 Sudden 遮挡的比例由 lane_cover 决定
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VisibilityMod {
+    Off,
+    /// 箭头接近目标时消失，遮挡跑道的后段
+    Hidden,
+    /// 箭头走过一段距离后才出现，遮挡跑道的前段
+    Sudden,
+    /// 整条跑道上都看不见箭头
+    Stealth,
+}
+
+impl Default for VisibilityMod {
+    fn default() -> Self {
+        VisibilityMod::Off
+    }
+}
+
+impl VisibilityMod {
+    pub const ALL: [VisibilityMod; 4] = [
+        VisibilityMod::Off,
+        VisibilityMod::Hidden,
+        VisibilityMod::Sudden,
+        VisibilityMod::Stealth,
+    ];
+    pub fn name(&self) -> &'static str {
+        match self {
+            VisibilityMod::Off => "Off",
+            VisibilityMod::Hidden => "Hidden",
+            VisibilityMod::Sudden => "Sudden",
+            VisibilityMod::Stealth => "Stealth",
+        }
+    }
+}
+
+/// 生成谱面时应用的 mod
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mods {
+    pub lane: LaneMod,
+    /// 箭头移动速度的倍数，只影响显示，不改变判定时间
+    pub hi_speed: f32,
+    pub visibility: VisibilityMod,
+    /// 遮挡跑道的比例（0 到 1）
+    pub lane_cover: f32,
+}
+
+impl Default for Mods {
+    fn default() -> Self {
+        Self {
+            lane: LaneMod::Off,
+            hi_speed: 1.0,
+            visibility: VisibilityMod::Off,
+            lane_cover: 0.5,
+        }
+    }
+}
+
+impl Mods {
+    /// 成绩是否计入排名，高速不影响
+    pub fn ranked(&self) -> bool {
+        self.lane.ranked()
+    }
+    /// 调整高速，保持在范围内
+    pub fn adjust_hi_speed(&mut self, delta: f32) {
+        self.hi_speed = (self.hi_speed + delta).max(MIN_HI_SPEED).min(MAX_HI_SPEED);
+    }
+    /// 调整遮挡比例，保持在 0 到 1
+    pub fn adjust_lane_cover(&mut self, delta: f32) {
+        let cover = (self.lane_cover + delta).max(0.0).min(1.0);
+        self.lane_cover = (cover * 100.0).round() / 100.0;
+    }
+    /// 箭头在跑道上的不透明度，progress 为从出发点（0）到目标（1）走过的比例，
+    /// 超过目标后继续增大
+    pub fn arrow_alpha(&self, progress: f32) -> f32 {
+        let cover = self.lane_cover;
+        let alpha = match self.visibility {
+            VisibilityMod::Off => 1.0,
+            VisibilityMod::Hidden => (1.0 - cover - progress) / FADE_LENGTH + 1.0,
+            VisibilityMod::Sudden => (progress - cover) / FADE_LENGTH,
+            VisibilityMod::Stealth => 0.0,
+        };
+        alpha.max(0.0).min(1.0)
+    }
+    /// 比如 "Mirror x1.50 Hidden 40%"，没有 mod 时为 "None"
+    pub fn name(&self) -> String {
+        let mut names = Vec::new();
+        if self.lane != LaneMod::Off {
+            names.push(self.lane.name().to_string());
+        }
+        if (self.hi_speed - 1.0).abs() > f32::EPSILON {
+            names.push(format!("x{:.2}", self.hi_speed));
+        }
+        if self.visibility == VisibilityMod::Stealth {
+            names.push(self.visibility.name().to_string());
+        } else if self.visibility != VisibilityMod::Off {
+            names.push(format!(
+                "{} {:.0}%",
+                self.visibility.name(),
+                self.lane_cover * 100.0
+            ));
+        }
+        if names.is_empty() {
+            String::from("None")
+        } else {
+            names.join(" ")
+        }
+    }
+    /// 改变箭头方向和速度，点击时间不变，结果按生成时间排序
+    pub fn apply(&self, arrows: &mut Vec<ArrowTime>) {
+        match self.lane {
+            LaneMod::Off => {}
+            LaneMod::Mirror => {
+                for arrow in arrows.iter_mut() {
+                    arrow.direction = mirror(arrow.direction);
+                }
+            }
+            LaneMod::Random => {
+                let mut lanes = Directions::directions();
+                fastrand::shuffle(&mut lanes);
+                for arrow in arrows.iter_mut() {
+                    arrow.direction = lanes[arrow.direction.index()];
+                }
+            }
+            LaneMod::Shuffle => shuffle(arrows),
+        }
+        for arrow in arrows.iter_mut() {
+            arrow.set_hi_speed(self.hi_speed);
+        }
+        arrows.sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
+    }
+}
+
+fn mirror(direction: Directions) -> Directions {
+    match direction {
+        Directions::Left => Directions::Right,
+        Directions::Right => Directions::Left,
+        direction => direction,
+    }
+}
+
+/// 按点击时间给每个箭头随机方向：同时按下的箭头方向不同，
+/// 并且尽量避开上一组箭头的方向，避免连打
+fn shuffle(arrows: &mut [ArrowTime]) {
+    let mut order: Vec<usize> = (0..arrows.len()).collect();
+    order.sort_by(|&a, &b| {
+        arrows[a]
+            .click_time()
+            .partial_cmp(&arrows[b].click_time())
+            .unwrap()
+    });
+    let mut previous: Vec<Directions> = Vec::new();
+    let mut chord: Vec<Directions> = Vec::new();
+    let mut chord_time = f64::MIN;
+    for i in order {
+        let time = arrows[i].click_time();
+        if time - chord_time > CHORD_WINDOW {
+            previous = std::mem::take(&mut chord);
+            chord_time = time;
+        }
+        let free: Vec<Directions> = Directions::directions()
+            .iter()
+            .copied()
+            .filter(|direction| !chord.contains(direction))
+            .collect();
+        let no_jack: Vec<Directions> = free
+            .iter()
+            .copied()
+            .filter(|direction| !previous.contains(direction))
+            .collect();
+        let candidates = if no_jack.is_empty() { free } else { no_jack };
+        // 同时按下的箭头超过方向数时保持原来的方向
+        if !candidates.is_empty() {
+            arrows[i].direction = candidates[fastrand::usize(..candidates.len())];
+        }
+        chord.push(arrows[i].direction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::types::{ArrowTimeToml, Speed};
+
+    fn arrow(click_time: f64, speed: Speed, direction: Directions) -> ArrowTime {
+        let toml = ArrowTimeToml {
+            click_time,
+            speed,
+            direction,
+            keysound: None,
+        };
+        ArrowTime::new(&toml, &HashMap::new())
+    }
+
+    /// 单个箭头和两三个同时按下的箭头交替，方向和速度各不相同，按生成时间排序
+    fn chart() -> Vec<ArrowTime> {
+        let speeds = [Speed::Slow, Speed::Medium, Speed::Fast];
+        let directions = Directions::directions();
+        let mut arrows = Vec::new();
+        for i in 0..60 {
+            let time = 2.0 + i as f64 * 0.25;
+            for j in 0..1 + i % 3 {
+                let direction = directions[(i + j) % directions.len()];
+                arrows.push(arrow(time, speeds[(i + j) % speeds.len()], direction));
+            }
+        }
+        Mods::default().apply(&mut arrows);
+        arrows
+    }
+
+    /// 按点击时间分组的方向
+    fn chords(arrows: &[ArrowTime]) -> Vec<Vec<Directions>> {
+        let mut arrows: Vec<_> = arrows.iter().collect();
+        arrows.sort_by(|a, b| a.click_time().partial_cmp(&b.click_time()).unwrap());
+        let mut chords: Vec<Vec<Directions>> = Vec::new();
+        let mut chord_time = f64::MIN;
+        for arrow in arrows {
+            if arrow.click_time() - chord_time > CHORD_WINDOW {
+                chords.push(Vec::new());
+                chord_time = arrow.click_time();
+            }
+            chords.last_mut().unwrap().push(arrow.direction);
+        }
+        chords
+    }
+
+    fn sorted_click_times(arrows: &[ArrowTime]) -> Vec<f64> {
+        let mut times: Vec<f64> = arrows.iter().map(ArrowTime::click_time).collect();
+        times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        times
+    }
+
+    #[test]
+    fn shuffle_keeps_chords_apart_and_avoids_jacks() {
+        let lanes = Directions::directions().len();
+        for seed in 0..20 {
+            fastrand::seed(seed);
+            let mut arrows = chart();
+            shuffle(&mut arrows);
+            let chords = chords(&arrows);
+            for (i, chord) in chords.iter().enumerate() {
+                for (j, direction) in chord.iter().enumerate() {
+                    assert!(!chord[j + 1..].contains(direction), "chord {:?}", chord);
+                }
+                if i == 0 {
+                    continue;
+                }
+                // 方向不够时才和上一组重复
+                let previous = &chords[i - 1];
+                let repeated = chord.iter().filter(|d| previous.contains(d)).count();
+                let unavoidable = (chord.len() + previous.len()).saturating_sub(lanes);
+                assert_eq!(repeated, unavoidable, "{:?} after {:?}", chord, previous);
+            }
+        }
+    }
+
+    #[test]
+    fn apply_keeps_click_times_and_sorts_by_spawn_time() {
+        let original = sorted_click_times(&chart());
+        for &lane in LaneMod::ALL.iter() {
+            for &hi_speed in &[MIN_HI_SPEED, 1.0, 1.75, MAX_HI_SPEED] {
+                let mods = Mods {
+                    lane,
+                    hi_speed,
+                    ..Default::default()
+                };
+                let mut arrows = chart();
+                mods.apply(&mut arrows);
+                let times = sorted_click_times(&arrows);
+                assert_eq!(times.len(), original.len());
+                for (time, expected) in times.iter().zip(&original) {
+                    assert!((time - expected).abs() < 1e-5, "{:?} {}", lane, hi_speed);
+                }
+                assert!(arrows
+                    .windows(2)
+                    .all(|pair| pair[0].spawn_time <= pair[1].spawn_time));
+            }
+        }
+    }
+
+    #[test]
+    fn mirror_only_swaps_left_and_right() {
+        let original = chart();
+        let mut arrows = original.clone();
+        Mods {
+            lane: LaneMod::Mirror,
+            ..Default::default()
+        }
+        .apply(&mut arrows);
+        for (arrow, before) in arrows.iter().zip(&original) {
+            let expected = match before.direction {
+                Directions::Left => Directions::Right,
+                Directions::Right => Directions::Left,
+                direction => direction,
+            };
+            assert_eq!(arrow.direction, expected);
+            assert!((arrow.click_time() - before.click_time()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn only_fixed_lanes_are_ranked() {
+        for &lane in LaneMod::ALL.iter() {
+            let mods = Mods {
+                lane,
+                hi_speed: 2.0,
+                visibility: VisibilityMod::Hidden,
+                ..Default::default()
+            };
+            let expected = matches!(lane, LaneMod::Off | LaneMod::Mirror);
+            assert_eq!(mods.ranked(), expected, "{:?}", lane);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use bevy::prelude::*;
+
+use crate::arrows::Arrow;
+use crate::consts::DELAY_SONG;
+use crate::score::ScoreResource;
+use crate::time::{ControlledTime, GameRate, GameStartTime};
+use crate::types::{ArrowTime, SectionToml, SongConfig, SongConfigToml};
+use crate::AppState;
+pub use setup::PracticeSetup;
+use setup::*;
+
+mod setup;
+
+/// 循环结束后等待多久（秒）重新开始，让最后的箭头判定完
+const LOOP_TAIL: f64 = 1.0;
+/// 第一个箭头出现前至少留出的时间（秒）
+const SPAWN_MARGIN: f64 = 0.1;
+
+/// 练习模式：循环谱面的一段，可以调整速度和预备拍，不记录成绩
+pub struct PracticePlugin;
+
+impl Plugin for PracticePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(AppState::Practice).with_system(setup_practice_menu.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Practice)
+                .with_system(practice_menu_keyboard.system())
+                .with_system(update_practice_menu_text.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Practice).with_system(despawn_practice_menu.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(AppState::Game).with_system(setup_practice_hud.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Game)
+                .with_system(repeat_loop.system())
+                .with_system(update_practice_hud.system())
+                .with_system(update_count_in.system()),
+        )
+        .add_system_set(SystemSet::on_exit(AppState::Game).with_system(end_practice.system()));
+    }
+}
+
+/// 正在练习，退出时返回练习设置
+pub struct PracticeLoop {
+    /// 循环内的箭头
+    arrows: Vec<ArrowTime>,
+    /// 每次循环开始的游戏时间
+    start_time: f64,
+    /// 循环的开始和结束时间（歌曲时间）
+    start: f64,
+    end: f64,
+    /// 每拍的长度（秒），预备拍按拍倒数
+    beat: f64,
+    lead_in_beats: u32,
+    rate: f64,
+    /// 第几次循环
+    count: u32,
+    /// 练习的谱面文件和资源，谱面被修改时重新载入
+    pub chart_file: Option<String>,
+    pub chart: Option<Handle<SongConfigToml>>,
+}
+
+/// 按练习设置生成循环的谱面，从预备拍开始
+fn start_practice(cmd: &mut Commands, setup: &PracticeSetup) {
+    let mut song = setup.song.clone();
+    song.arrows.retain(|arrow| {
+        let click_time = arrow.click_time();
+        click_time >= setup.start && click_time <= setup.end
+    });
+    // 预备拍之前开始，并且要在第一个箭头出现之前
+    let first_spawn = song
+        .arrows
+        .first()
+        .map_or(setup.start, |arrow| arrow.spawn_time);
+    let start = (setup.start - setup.lead_in()).min(first_spawn - SPAWN_MARGIN);
+    let start_time = (start + DELAY_SONG).max(0.0);
+    cmd.insert_resource(PracticeLoop {
+        arrows: song.arrows.clone(),
+        start_time,
+        start: setup.start,
+        end: setup.end,
+        beat: setup.beat,
+        lead_in_beats: setup.lead_in_beats,
+        rate: setup.rate,
+        count: 1,
+        chart_file: setup.chart_file.clone(),
+        chart: setup.chart.clone(),
+    });
+    cmd.insert_resource(GameStartTime(start_time));
+    cmd.insert_resource(GameRate(setup.rate));
+    cmd.insert_resource(song);
+}
+
+/// 循环结束后跳回循环开始，重新生成箭头并清空成绩
+fn repeat_loop(
+    mut cmd: Commands,
+    practice: Option<ResMut<PracticeLoop>>,
+    mut time: ResMut<ControlledTime>,
+    mut config: ResMut<SongConfig>,
+    mut score: ResMut<ScoreResource>,
+    arrows: Query<Entity, With<Arrow>>,
+) {
+    let mut practice = match practice {
+        Some(practice) => practice,
+        None => return,
+    };
+    if time.seconds_since_startup() - DELAY_SONG <= practice.end + LOOP_TAIL {
+        return;
+    }
+    arrows.for_each(|e| cmd.entity(e).despawn());
+    config.arrows = practice.arrows.clone();
+    config.finished = false;
+    *score = ScoreResource::new(config.arrows.len());
+    time.seek(practice.start_time);
+    practice.count += 1;
+}
+
+struct PracticeHud;
+/// 预备拍的倒数
+struct CountIn;
+
+fn setup_practice_hud(
+    mut cmd: Commands,
+    practice: Option<Res<PracticeLoop>>,
+    asset_server: Res<AssetServer>,
+) {
+    if practice.is_none() {
+        return;
+    }
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    cmd.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Percent(40.0),
+                top: Val::Px(10.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size: 24.0,
+                color: Color::rgb(0.55, 0.75, 0.55),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    })
+    .insert(PracticeHud);
+    cmd.spawn_bundle(TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                left: Val::Percent(48.0),
+                top: Val::Percent(40.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font,
+                font_size: 96.0,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    })
+    .insert(PracticeHud)
+    .insert(CountIn);
+}
+
+/// 显示速度和循环次数
+fn update_practice_hud(
+    practice: Option<Res<PracticeLoop>>,
+    texts: Query<(&mut Text, ChangeTrackers<PracticeHud>), Without<CountIn>>,
+) {
+    let practice = match practice {
+        Some(practice) => practice,
+        None => return,
+    };
+    texts.for_each_mut(|(mut text, tracker)| {
+        if practice.is_changed() || tracker.is_added() {
+            text.sections[0].value = format!(
+                "Practice {:.0}%  Loop {}",
+                practice.rate * 100.0,
+                practice.count
+            );
+        }
+    });
+}
+
+/// 预备拍中倒数剩下的拍数，循环开始后隐藏
+fn update_count_in(
+    practice: Option<Res<PracticeLoop>>,
+    time: Res<ControlledTime>,
+    texts: Query<&mut Text, With<CountIn>>,
+) {
+    let practice = match practice {
+        Some(practice) => practice,
+        None => return,
+    };
+    let remaining = practice.start - (time.seconds_since_startup() - DELAY_SONG);
+    let value = count_in(remaining, practice.beat, practice.lead_in_beats)
+        .map_or(String::new(), |beats| beats.to_string());
+    texts.for_each_mut(|mut text| {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    });
+}
+
+/// 距离循环开始还有 remaining 秒时倒数的拍数，预备拍之外为 None
+fn count_in(remaining: f64, beat: f64, lead_in_beats: u32) -> Option<u32> {
+    let beats = (remaining / beat).ceil();
+    if remaining > 0.0 && beats <= lead_in_beats as f64 {
+        Some(beats as u32)
+    } else {
+        None
+    }
+}
+
+/// 离开游戏时结束练习，恢复正常的开始时间和速度
+fn end_practice(
+    mut cmd: Commands,
+    practice: Option<Res<PracticeLoop>>,
+    mut start_time: ResMut<GameStartTime>,
+    mut rate: ResMut<GameRate>,
+    hud: Query<Entity, With<PracticeHud>>,
+) {
+    if practice.is_none() {
+        return;
+    }
+    hud.for_each(|e| cmd.entity(e).despawn_recursive());
+    cmd.remove_resource::<PracticeLoop>();
+    start_time.0 = 0.0;
+    rate.0 = 1.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn count_in_counts_down_whole_beats() {
+        assert_eq!(count_in(2.0, 0.5, 4), Some(4));
+        assert_eq!(count_in(1.9, 0.5, 4), Some(4));
+        assert_eq!(count_in(1.5, 0.5, 4), Some(3));
+        assert_eq!(count_in(0.6, 0.5, 4), Some(2));
+        assert_eq!(count_in(0.1, 0.5, 4), Some(1));
+    }
+
+    #[test]
+    fn count_in_is_hidden_outside_the_lead_in() {
+        // 为第一个箭头留出的时间比预备拍长
+        assert_eq!(count_in(2.1, 0.5, 4), None);
+        assert_eq!(count_in(0.0, 0.5, 4), None);
+        assert_eq!(count_in(-0.3, 0.5, 4), None);
+        assert_eq!(count_in(0.4, 0.5, 0), None);
+    }
+}
@@ -0,0 +1,380 @@
+use super::*;
+
+/// 没有 bpm 的谱面按这个 bpm 计算小节和预备拍
+const DEFAULT_BPM: f64 = 120.0;
+/// 每小节的拍数，左右调整开始/结束时间时每次移动一小节
+const BEATS_PER_MEASURE: f64 = 4.0;
+/// 每次调整的速度
+const RATE_STEP: f64 = 0.05;
+const MIN_RATE: f64 = 0.5;
+const MAX_RATE: f64 = 1.5;
+/// 预备拍数的上限
+const MAX_LEAD_IN_BEATS: u32 = 16;
+
+/// 练习设置的选项
+#[derive(Copy, Clone, PartialEq)]
+enum PracticeOption {
+    Start,
+    End,
+    Rate,
+    LeadIn,
+}
+
+impl PracticeOption {
+    const ALL: [PracticeOption; 4] = [
+        PracticeOption::Start,
+        PracticeOption::End,
+        PracticeOption::Rate,
+        PracticeOption::LeadIn,
+    ];
+}
+
+/// 练习的谱面和循环设置，从练习中返回时保留
+pub struct PracticeSetup {
+    /// 完整的谱面，不记录成绩
+    pub(super) song: SongConfig,
+    /// 练习的谱面文件和资源，谱面被修改时从这里重新载入
+    pub(super) chart_file: Option<String>,
+    pub(super) chart: Option<Handle<SongConfigToml>>,
+    sections: Vec<SectionToml>,
+    /// 每拍的长度（秒）
+    pub(super) beat: f64,
+    /// 最后一个箭头之后一秒
+    length: f64,
+    /// 循环的开始和结束时间（秒）
+    pub(super) start: f64,
+    pub(super) end: f64,
+    /// 选中的段落
+    section: Option<usize>,
+    /// 播放速度，0.5 到 1.5
+    pub(super) rate: f64,
+    /// 循环开始前的预备拍数
+    pub(super) lead_in_beats: u32,
+    cursor: usize,
+}
+
+impl PracticeSetup {
+    /// 由谱面和生成的配置开始练习设置，配置不记录成绩；
+    /// 谱面文件留在练习设置里，游戏中不按谱面记录
+    pub fn new(chart: &SongConfigToml, mut song: SongConfig) -> Self {
+        let chart_file = song.chart_file.take();
+        let chart_handle = song.chart.take();
+        song.chart_hash = None;
+        let mut sections = chart.sections.clone();
+        sections.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        let length = chart.last_click_time().map_or(0.0, |time| time + 1.0);
+        Self {
+            song,
+            chart_file,
+            chart: chart_handle,
+            sections,
+            beat: 60.0 / chart.bpm.unwrap_or(DEFAULT_BPM),
+            length,
+            start: 0.0,
+            end: length,
+            section: None,
+            rate: 1.0,
+            lead_in_beats: 4,
+            cursor: 0,
+        }
+    }
+    /// 预备拍的长度（秒）
+    pub(super) fn lead_in(&self) -> f64 {
+        self.lead_in_beats as f64 * self.beat
+    }
+    /// 选中上一个或下一个段落，循环这个段落
+    fn select_section(&mut self, step: isize) {
+        if self.sections.is_empty() {
+            return;
+        }
+        let last = self.sections.len() as isize - 1;
+        let index = match self.section {
+            Some(index) => (index as isize + step).max(0).min(last),
+            None if step > 0 => 0,
+            None => last,
+        } as usize;
+        self.section = Some(index);
+        self.start = self.sections[index].time;
+        self.end = self
+            .sections
+            .get(index + 1)
+            .map_or(self.length, |next| next.time)
+            .max(self.start + self.beat);
+    }
+    fn adjust(&mut self, option: PracticeOption, step: f64) {
+        let measure = self.beat * BEATS_PER_MEASURE;
+        match option {
+            PracticeOption::Start => {
+                self.start = (self.start + step * measure)
+                    .max(0.0)
+                    .min(self.end - self.beat);
+                self.section = None;
+            }
+            PracticeOption::End => {
+                self.end = (self.end + step * measure)
+                    .max(self.start + self.beat)
+                    .min(self.length.max(self.start + self.beat));
+                self.section = None;
+            }
+            PracticeOption::Rate => {
+                let rate = (self.rate + step * RATE_STEP).max(MIN_RATE).min(MAX_RATE);
+                self.rate = (rate * 100.0).round() / 100.0;
+            }
+            PracticeOption::LeadIn => {
+                let beats = self.lead_in_beats as i32 + step as i32;
+                self.lead_in_beats = beats.max(0).min(MAX_LEAD_IN_BEATS as i32) as u32;
+            }
+        }
+    }
+    fn label(&self, option: PracticeOption) -> String {
+        match option {
+            PracticeOption::Start => {
+                let section = self
+                    .section
+                    .map_or(String::new(), |i| format!(" ({})", self.sections[i].name));
+                format!("Start: {}{}", format_time(self.start), section)
+            }
+            PracticeOption::End => format!("End: {}", format_time(self.end)),
+            PracticeOption::Rate => format!("Rate: {:.0}%", self.rate * 100.0),
+            PracticeOption::LeadIn => format!("Lead-in: {} beats", self.lead_in_beats),
+        }
+    }
+}
+
+/// 分:秒.十分之一秒
+fn format_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}:{:04.1}", minutes, seconds - minutes * 60.0)
+}
+
+pub(super) struct PracticeSetupUI;
+/// 选项的文字
+pub(super) struct PracticeOptionText(PracticeOption);
+
+/// 练习设置界面
+pub(super) fn setup_practice_menu(
+    mut cmd: Commands,
+    setup: Res<PracticeSetup>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = |font_size: f32| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let text = |value: String, font_size: f32| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(8.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(value, text_style(font_size), Default::default()),
+        ..Default::default()
+    };
+    let sections = if setup.sections.is_empty() {
+        String::from("No sections")
+    } else {
+        format!("{} sections", setup.sections.len())
+    };
+    cmd.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: materials.add(Color::NONE.into()),
+        ..Default::default()
+    })
+    .insert(PracticeSetupUI)
+    .with_children(|parent| {
+        parent.spawn_bundle(text(format!("Practice: {}", setup.song.name), 36.0));
+        parent.spawn_bundle(text(sections, 16.0));
+        for option in PracticeOption::ALL.iter() {
+            parent
+                .spawn_bundle(text(String::new(), 24.0))
+                .insert(PracticeOptionText(*option));
+        }
+        parent.spawn_bundle(text(
+            String::from("Up/Down: select  Left/Right: adjust\nPgUp/PgDn: section  Enter: start"),
+            16.0,
+        ));
+    });
+}
+
+/// 上下选择选项，左右调整，PgUp/PgDn 选择段落，回车开始练习
+pub(super) fn practice_menu_keyboard(
+    mut cmd: Commands,
+    key_input: Res<Input<KeyCode>>,
+    mut setup: ResMut<PracticeSetup>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let len = PracticeOption::ALL.len();
+    if key_input.just_pressed(KeyCode::Up) {
+        setup.cursor = (setup.cursor + len - 1) % len;
+    }
+    if key_input.just_pressed(KeyCode::Down) {
+        setup.cursor = (setup.cursor + 1) % len;
+    }
+    let option = PracticeOption::ALL[setup.cursor];
+    if key_input.just_pressed(KeyCode::Left) {
+        setup.adjust(option, -1.0);
+    }
+    if key_input.just_pressed(KeyCode::Right) {
+        setup.adjust(option, 1.0);
+    }
+    if key_input.just_pressed(KeyCode::PageUp) {
+        setup.select_section(-1);
+    }
+    if key_input.just_pressed(KeyCode::PageDown) {
+        setup.select_section(1);
+    }
+    if key_input.just_pressed(KeyCode::Return) {
+        start_practice(&mut cmd, &setup);
+        state.set(AppState::Game).unwrap();
+    }
+}
+
+/// 更新选项文字，选中的选项高亮
+pub(super) fn update_practice_menu_text(
+    setup: Res<PracticeSetup>,
+    added: Query<(), Added<PracticeOptionText>>,
+    q: Query<(&mut Text, &PracticeOptionText)>,
+) {
+    if !(setup.is_changed() || added.iter().next().is_some()) {
+        return;
+    }
+    q.for_each_mut(|(mut text, option_text)| {
+        let option = option_text.0;
+        let selected = PracticeOption::ALL[setup.cursor] == option;
+        text.sections[0].value = setup.label(option);
+        text.sections[0].style.color = if selected {
+            Color::rgb(0.55, 0.75, 0.55)
+        } else {
+            Color::rgb(0.9, 0.9, 0.9)
+        };
+    });
+}
+
+pub(super) fn despawn_practice_menu(mut cmd: Commands, q: Query<Entity, With<PracticeSetupUI>>) {
+    q.for_each(|e| cmd.entity(e).despawn_recursive());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SongSounds;
+
+    /// 测试谱面（最后一个箭头在 11 秒）加上 bpm 和乱序的段落
+    fn setup(sections: &str) -> PracticeSetup {
+        let contents = format!(
+            "bpm = 120.0\nsections = [{}]\n{}",
+            sections,
+            include_str!("../../assets/songs/test.toml")
+        );
+        let chart = SongConfigToml::parse("test.toml", &contents).unwrap();
+        let song = SongConfig::new(
+            chart.name.clone(),
+            Default::default(),
+            &chart.arrows,
+            SongSounds::default(),
+        );
+        PracticeSetup::new(&chart, song)
+    }
+
+    fn with_sections() -> PracticeSetup {
+        setup(
+            r#"{ name = "Verse", time = 4.0 }, { name = "Intro", time = 0.0 },
+            { name = "Chorus", time = 8.0 }"#,
+        )
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} vs {}",
+            actual,
+            expected
+        );
+    }
+
+    fn adjust_times(setup: &mut PracticeSetup, option: PracticeOption, step: f64, times: usize) {
+        for _ in 0..times {
+            setup.adjust(option, step);
+        }
+    }
+
+    #[test]
+    fn rate_is_clamped() {
+        let mut setup = setup("");
+        assert_near(setup.rate, 1.0);
+        setup.adjust(PracticeOption::Rate, 1.0);
+        assert_near(setup.rate, 1.05);
+        adjust_times(&mut setup, PracticeOption::Rate, 1.0, 20);
+        assert_near(setup.rate, MAX_RATE);
+        adjust_times(&mut setup, PracticeOption::Rate, -1.0, 40);
+        assert_near(setup.rate, MIN_RATE);
+    }
+
+    #[test]
+    fn lead_in_is_clamped() {
+        let mut setup = setup("");
+        assert_eq!(setup.lead_in_beats, 4);
+        assert_near(setup.lead_in(), 2.0);
+        adjust_times(&mut setup, PracticeOption::LeadIn, 1.0, 20);
+        assert_eq!(setup.lead_in_beats, MAX_LEAD_IN_BEATS);
+        adjust_times(&mut setup, PracticeOption::LeadIn, -1.0, 20);
+        assert_eq!(setup.lead_in_beats, 0);
+        assert_near(setup.lead_in(), 0.0);
+    }
+
+    #[test]
+    fn sections_loop_until_the_next_one() {
+        let mut setup = with_sections();
+        let mut selected = Vec::new();
+        for _ in 0..4 {
+            setup.select_section(1);
+            selected.push((setup.section, setup.start, setup.end));
+        }
+        // 最后一个段落到最后一个箭头之后一秒，已经是最后一个时不再移动
+        assert_eq!(
+            selected,
+            vec![
+                (Some(0), 0.0, 4.0),
+                (Some(1), 4.0, 8.0),
+                (Some(2), 8.0, 12.0),
+                (Some(2), 8.0, 12.0),
+            ]
+        );
+        assert!(setup.label(PracticeOption::Start).contains("Chorus"));
+        setup.select_section(-1);
+        assert_eq!((setup.start, setup.end), (4.0, 8.0));
+    }
+
+    #[test]
+    fn selecting_backwards_starts_from_the_last_section() {
+        let mut setup = with_sections();
+        setup.select_section(-1);
+        assert_eq!(setup.section, Some(2));
+        assert_near(setup.start, 8.0);
+    }
+
+    #[test]
+    fn adjusting_the_loop_deselects_the_section() {
+        let mut setup = with_sections();
+        setup.select_section(1);
+        setup.adjust(PracticeOption::End, 1.0);
+        assert_eq!(setup.section, None);
+        assert_eq!((setup.start, setup.end), (0.0, 6.0));
+    }
+
+    #[test]
+    fn no_sections_keeps_the_whole_chart() {
+        let mut setup = setup("");
+        setup.select_section(1);
+        assert_eq!(setup.section, None);
+        assert_eq!((setup.start, setup.end), (0.0, 12.0));
+    }
+}
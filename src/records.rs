@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::arrows::SongFinishedEvent;
+use crate::mods::Mods;
+use crate::score::{ClearLamp, Grade, ScoreResource};
+use crate::types::SongConfig;
+use crate::AppState;
+
+/// 游玩记录文件
+const RECORDS_FILE: &str = "records.toml";
+/// 成绩记录文件
+const SCORES_FILE: &str = "scores.toml";
+
+pub struct RecordsPlugin;
+
+impl Plugin for RecordsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(PlayRecords::load())
+            .insert_resource(ScoreRecords::load())
+            .add_system_set(
+                SystemSet::on_enter(AppState::Game)
+                    .with_system(record_play.system())
+                    .with_system(count_play.system()),
+            )
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(record_clear.system()))
+            // 游玩中只修改内存中的记录，显示成绩时和退出时再写入文件
+            .add_system_set(
+                SystemSet::on_enter(AppState::Results).with_system(save_records.system()),
+            )
+            .add_system_to_stage(CoreStage::Last, save_records_on_exit.system());
+    }
+}
+
+/// 每个谱面的游玩记录，保存在 records.toml
+#[derive(Default, Serialize, Deserialize)]
+pub struct PlayRecords {
+    /// 谱面文件（相对于 assets/songs）到记录
+    #[serde(default)]
+    songs: HashMap<String, SongRecord>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SongRecord {
+    /// 最后一次游玩的时间（Unix 时间戳，秒）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_played: Option<u64>,
+    /// 是否没有失败地打完过整首歌
+    pub cleared: bool,
+}
+
+impl PlayRecords {
+    /// 读取记录，文件不存在或格式错误时为空
+    pub fn load() -> Self {
+        let contents = match std::fs::read_to_string(RECORDS_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+        toml::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("couldn't parse {}: {}", RECORDS_FILE, e);
+            Self::default()
+        })
+    }
+    pub fn save(&self) {
+        let toml_text = toml::to_string_pretty(self).expect("couldn't convert to toml text");
+        if let Err(e) = std::fs::write(RECORDS_FILE, toml_text) {
+            eprintln!("couldn't write {}: {}", RECORDS_FILE, e);
+        }
+    }
+    pub fn get(&self, chart_file: &str) -> Option<&SongRecord> {
+        self.songs.get(chart_file)
+    }
+    fn entry(&mut self, chart_file: &str) -> &mut SongRecord {
+        self.songs.entry(chart_file.to_string()).or_default()
+    }
+}
+
+/// 每个谱面的最好成绩，按谱面内容的哈希记录，保存在 scores.toml
+/// 谱面修改后哈希改变，成绩重新记录
+#[derive(Default, Serialize, Deserialize)]
+pub struct ScoreRecords {
+    #[serde(default)]
+    charts: HashMap<String, ChartScore>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChartScore {
+    pub best_score: usize,
+    /// 最高准确率（0 到 100）
+    pub best_accuracy: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_grade: Option<Grade>,
+    /// 最好的通关标记，没有打完过时为 None
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lamp: Option<ClearLamp>,
+    /// 最高分使用的 mod
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best_mods: Option<ModsRecord>,
+    /// 开始游玩的次数
+    pub play_count: u32,
+    /// 最近一次没有失败地打完的时间误差，用于分析整体偏早还是偏晚
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_timing: Option<TimingRecord>,
+}
+
+/// 成绩使用的 mod，以及当时是否计入排名
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ModsRecord {
+    pub mods: Mods,
+    pub ranked: bool,
+}
+
+impl ModsRecord {
+    pub fn new(mods: Mods) -> Self {
+        Self {
+            mods,
+            ranked: mods.ranked(),
+        }
+    }
+    /// 比如 "Mirror x1.50 (unranked)"
+    pub fn name(&self) -> String {
+        if self.ranked {
+            self.mods.name()
+        } else {
+            format!("{} (unranked)", self.mods.name())
+        }
+    }
+}
+
+/// 一次游玩的时间误差统计
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TimingRecord {
+    /// 平均误差（毫秒），负数表示偏早
+    pub mean_ms: f32,
+    /// 标准差（毫秒）
+    pub std_dev_ms: f32,
+    pub unstable_rate: f32,
+    /// 每次击中的（歌曲时间（秒），误差（毫秒））
+    pub offsets: Vec<(f64, f32)>,
+}
+
+impl TimingRecord {
+    /// 没有击中任何箭头时为 None
+    fn new(score: &ScoreResource) -> Option<Self> {
+        Some(Self {
+            mean_ms: score.mean_offset_ms()?,
+            std_dev_ms: score.offset_std_dev_ms()?,
+            unstable_rate: score.unstable_rate()?,
+            offsets: score
+                .offsets()
+                .iter()
+                .map(|hit| (hit.time, hit.offset * 1000.0))
+                .collect(),
+        })
+    }
+}
+
+impl ScoreRecords {
+    /// 读取成绩，文件不存在或格式错误时为空
+    pub fn load() -> Self {
+        let contents = match std::fs::read_to_string(SCORES_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+        toml::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("couldn't parse {}: {}", SCORES_FILE, e);
+            Self::default()
+        })
+    }
+    pub fn save(&self) {
+        let toml_text = toml::to_string_pretty(self).expect("couldn't convert to toml text");
+        if let Err(e) = std::fs::write(SCORES_FILE, toml_text) {
+            eprintln!("couldn't write {}: {}", SCORES_FILE, e);
+        }
+    }
+    pub fn get(&self, hash: &str) -> Option<&ChartScore> {
+        self.charts.get(hash)
+    }
+    /// 记录歌曲结束时的成绩，分别保留每一项的最好成绩；失败时只记录通关标记
+    pub fn record(&mut self, hash: &str, score: &ScoreResource, mods: Mods) {
+        let best = self.charts.entry(hash.to_string()).or_default();
+        best.lamp = best.lamp.max(Some(score.lamp()));
+        if score.failed() {
+            return;
+        }
+        if best.best_mods.is_none() || score.score() > best.best_score {
+            best.best_mods = Some(ModsRecord::new(mods));
+        }
+        best.best_score = best.best_score.max(score.score());
+        best.best_accuracy = best.best_accuracy.max(score.accuracy());
+        best.best_grade = best.best_grade.max(Some(score.grade()));
+        best.last_timing = TimingRecord::new(score);
+    }
+}
+
+/// 开始游戏时记录游玩时间，试玩不记录
+fn record_play(config: Res<SongConfig>, mut records: ResMut<PlayRecords>) {
+    if let Some(chart_file) = &config.chart_file {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_secs());
+        records.entry(chart_file).last_played = Some(now);
+    }
+}
+
+/// 开始游戏时增加游玩次数，试玩不记录
+fn count_play(config: Res<SongConfig>, mut scores: ResMut<ScoreRecords>) {
+    if let Some(hash) = &config.chart_hash {
+        scores.charts.entry(hash.clone()).or_default().play_count += 1;
+    }
+}
+
+/// 没有失败地打完整首歌时记录通关
+fn record_clear(
+    mut finished_event: EventReader<SongFinishedEvent>,
+    config: Res<SongConfig>,
+    score: Res<ScoreResource>,
+    mut records: ResMut<PlayRecords>,
+) {
+    if finished_event.iter().next().is_none() || score.failed() {
+        return;
+    }
+    if let Some(chart_file) = &config.chart_file {
+        records.entry(chart_file).cleared = true;
+    }
+}
+
+fn save_records(records: Res<PlayRecords>, scores: Res<ScoreRecords>) {
+    records.save();
+    scores.save();
+}
+
+/// 在关闭窗口发出 AppExit 的同一帧保存，中途退出的游玩也会记录
+fn save_records_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    records: Res<PlayRecords>,
+    scores: Res<ScoreRecords>,
+) {
+    if exit_events.iter().next().is_some() {
+        save_records(records, scores);
+    }
+}
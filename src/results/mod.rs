@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+
+use crate::arrows::SongFinishedEvent;
+use crate::records::{ChartScore, ModsRecord, ScoreRecords};
+use crate::score::{Player2Score, ScoreResource};
+use crate::time::ControlledTime;
+use crate::types::{Player, SongConfig};
+use crate::AppState;
+use timing::*;
+use versus::*;
+
+mod timing;
+mod versus;
+
+/// 最后一个箭头判定或失败后，等待多久（秒）显示成绩
+const RESULTS_DELAY: f64 = 2.0;
+
+/// 歌曲结束后保存成绩并显示成绩界面
+pub struct ResultsPlugin;
+
+impl Plugin for ResultsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(SystemSet::on_enter(AppState::Game).with_system(clear_result.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(finish_play.system())
+                    .with_system(show_results.system()),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Results).with_system(setup_results.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Results).with_system(results_keyboard.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Results).with_system(despawn_results.system()),
+            );
+    }
+}
+
+/// 一次游玩的成绩
+pub struct PlayResult {
+    name: String,
+    score: ScoreResource,
+    mods: ModsRecord,
+    /// 这次之前的最好成绩
+    previous: Option<ChartScore>,
+    play_count: u32,
+    /// 歌曲结束的时间
+    finished_at: f64,
+    /// 双人对战时 2P 的成绩
+    rival: Option<ScoreResource>,
+}
+
+fn clear_result(mut cmd: Commands) {
+    cmd.remove_resource::<PlayResult>();
+}
+
+/// 歌曲结束或失败时记录成绩，试玩不保存也不显示成绩，双人对战只显示不保存
+fn finish_play(
+    mut cmd: Commands,
+    mut finished_event: EventReader<SongFinishedEvent>,
+    config: Res<SongConfig>,
+    score: Res<ScoreResource>,
+    score_2p: Res<Player2Score>,
+    mut scores: ResMut<ScoreRecords>,
+    time: Res<ControlledTime>,
+) {
+    if finished_event.iter().next().is_none() {
+        return;
+    }
+    if config.versus {
+        cmd.insert_resource(PlayResult {
+            name: config.name.clone(),
+            score: score.clone(),
+            mods: ModsRecord::new(config.mods),
+            previous: None,
+            play_count: 0,
+            finished_at: time.seconds_since_startup(),
+            rival: Some(score_2p.0.clone()),
+        });
+        return;
+    }
+    let hash = match &config.chart_hash {
+        Some(hash) => hash,
+        None => return,
+    };
+    let previous = scores
+        .get(hash)
+        .filter(|best| best.best_grade.is_some())
+        .cloned();
+    scores.record(hash, &score, config.mods);
+    cmd.insert_resource(PlayResult {
+        name: config.name.clone(),
+        score: score.clone(),
+        mods: ModsRecord::new(config.mods),
+        previous,
+        play_count: scores.get(hash).map_or(1, |best| best.play_count),
+        finished_at: time.seconds_since_startup(),
+        rival: None,
+    });
+}
+
+/// 歌曲结束一段时间后进入成绩界面
+fn show_results(
+    mut state: ResMut<State<AppState>>,
+    result: Option<Res<PlayResult>>,
+    time: Res<ControlledTime>,
+) {
+    if let Some(result) = result {
+        if time.seconds_since_startup() - result.finished_at >= RESULTS_DELAY {
+            state.set(AppState::Results).unwrap();
+        }
+    }
+}
+
+struct ResultsUI;
+
+fn setup_results(
+    mut cmd: Commands,
+    result: Res<PlayResult>,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+    if let Some(rival) = &result.rival {
+        let none = materials.add(Color::NONE.into());
+        spawn_versus_results(&mut cmd, &result, rival, font, none);
+        return;
+    }
+    let text_style = |font_size: f32| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    let score = &result.score;
+    // 失败的成绩不记录为最好成绩
+    let cleared = !score.failed();
+    let best_score = result.previous.as_ref().map_or(0, |best| best.best_score);
+    let new_best = if cleared && score.score() > best_score {
+        " (new best!)"
+    } else {
+        ""
+    };
+    let best = match &result.previous {
+        Some(best) if cleared => format!(
+            "Best: {:07} / {:.2}%",
+            best.best_score.max(score.score()),
+            best.best_accuracy.max(score.accuracy())
+        ),
+        Some(best) => format!("Best: {:07} / {:.2}%", best.best_score, best.best_accuracy),
+        None if cleared => String::from("First clear!"),
+        None => String::from("Not cleared yet"),
+    };
+    let lines = [
+        (result.name.clone(), 40.0),
+        (score.grade().name().to_string(), 80.0),
+        (score.lamp().name().to_string(), 30.0),
+        (format!("Score: {:07}{}", score.score(), new_best), 30.0),
+        (format!("Accuracy: {:.2}%", score.accuracy()), 30.0),
+        (
+            format!(
+                "Perfect: {}  Great: {}\nGood: {}  Miss: {}",
+                score.perfects(),
+                score.greats(),
+                score.goods(),
+                score.fails()
+            ),
+            20.0,
+        ),
+        (format!("Mods: {}", result.mods.name()), 20.0),
+        (best, 20.0),
+        (format!("Plays: {}", result.play_count), 20.0),
+        (String::from("Press Enter to continue"), 20.0),
+    ];
+    let none = materials.add(Color::NONE.into());
+    let column = || NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::ColumnReverse,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: none.clone(),
+        ..Default::default()
+    };
+    let timing_materials = TimingMaterials::new(&mut materials);
+    // node > 左边成绩，右边时间误差
+    cmd.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::Row,
+            justify_content: JustifyContent::SpaceAround,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: none.clone(),
+        ..Default::default()
+    })
+    .insert(ResultsUI)
+    .with_children(|parent| {
+        parent.spawn_bundle(column()).with_children(|parent| {
+            for (line, font_size) in lines.iter() {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(5.0)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        line.clone(),
+                        text_style(*font_size),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+        });
+        parent.spawn_bundle(column()).with_children(|parent| {
+            spawn_timing_panel(parent, score, text_style(18.0), &timing_materials);
+        });
+    });
+}
+
+/// 按 Enter 或空格返回菜单
+fn results_keyboard(mut state: ResMut<State<AppState>>, mut key_input: ResMut<Input<KeyCode>>) {
+    if key_input.just_pressed(KeyCode::Return) || key_input.just_pressed(KeyCode::Space) {
+        // 切换状态后菜单在同一帧运行，不清除的话会用这次按键开始选中的歌曲
+        key_input.reset(KeyCode::Return);
+        key_input.reset(KeyCode::Space);
+        state.set(AppState::Menu).unwrap();
+    }
+}
+
+fn despawn_results(mut cmd: Commands, q: Query<Entity, With<ResultsUI>>) {
+    q.for_each(|e| cmd.entity(e).despawn_recursive());
+}
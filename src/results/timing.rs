@@ -0,0 +1,178 @@
+use super::*;
+use crate::hit_error::MAX_OFFSET_MS;
+use crate::score::HitOffset;
+
+/// 图表大小
+const CHART_WIDTH: f32 = 360.0;
+const CHART_HEIGHT: f32 = 130.0;
+/// 直方图的柱数，中间一柱对应零误差
+const HISTOGRAM_BINS: usize = 21;
+
+/// 图表使用的颜色
+pub(super) struct TimingMaterials {
+    background: Handle<ColorMaterial>,
+    early: Handle<ColorMaterial>,
+    late: Handle<ColorMaterial>,
+    center: Handle<ColorMaterial>,
+}
+
+impl TimingMaterials {
+    pub(super) fn new(materials: &mut Assets<ColorMaterial>) -> Self {
+        Self {
+            background: materials.add(Color::rgba(0.15, 0.15, 0.15, 0.8).into()),
+            early: materials.add(Color::rgb(0.4, 0.7, 1.0).into()),
+            late: materials.add(Color::rgb(1.0, 0.55, 0.3).into()),
+            center: materials.add(Color::rgb(0.9, 0.9, 0.9).into()),
+        }
+    }
+    /// 提前为蓝色，延后为橙色
+    fn offset(&self, offset_ms: f32) -> Handle<ColorMaterial> {
+        if offset_ms < 0.0 {
+            self.early.clone()
+        } else {
+            self.late.clone()
+        }
+    }
+}
+
+/// 平均误差、标准差和不稳定度
+fn timing_summary(score: &ScoreResource) -> String {
+    match (
+        score.mean_offset_ms(),
+        score.offset_std_dev_ms(),
+        score.unstable_rate(),
+    ) {
+        (Some(mean), Some(std_dev), Some(unstable_rate)) => {
+            let tendency = if mean < 0.0 { "early" } else { "late" };
+            format!(
+                "Mean: {:+.1}ms ({})\nStd dev: {:.1}ms  UR: {:.1}",
+                mean, tendency, std_dev, unstable_rate
+            )
+        }
+        _ => String::from("No hits"),
+    }
+}
+
+/// 时间误差面板：统计、误差直方图和误差随时间的变化
+pub(super) fn spawn_timing_panel(
+    parent: &mut ChildBuilder,
+    score: &ScoreResource,
+    text_style: TextStyle,
+    materials: &TimingMaterials,
+) {
+    let text = |value: String| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(5.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(value, text_style.clone(), Default::default()),
+        ..Default::default()
+    };
+    parent.spawn_bundle(text(timing_summary(score)));
+    parent.spawn_bundle(text(String::from("Offsets (early - late)")));
+    spawn_histogram(parent, score.offsets(), materials);
+    parent.spawn_bundle(text(String::from("Offset over time")));
+    spawn_offset_graph(parent, score.offsets(), materials);
+}
+
+fn chart_bundle(materials: &TimingMaterials, style: Style) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            size: Size::new(Val::Px(CHART_WIDTH), Val::Px(CHART_HEIGHT)),
+            ..style
+        },
+        material: materials.background.clone(),
+        ..Default::default()
+    }
+}
+
+/// 误差所在的柱，超出范围的算在两端
+fn histogram_bin(offset_ms: f32) -> usize {
+    let ratio = (offset_ms + MAX_OFFSET_MS) / (2.0 * MAX_OFFSET_MS);
+    ((ratio * HISTOGRAM_BINS as f32).max(0.0) as usize).min(HISTOGRAM_BINS - 1)
+}
+
+fn spawn_histogram(parent: &mut ChildBuilder, offsets: &[HitOffset], materials: &TimingMaterials) {
+    let mut counts = [0usize; HISTOGRAM_BINS];
+    for hit in offsets {
+        counts[histogram_bin(hit.offset * 1000.0)] += 1;
+    }
+    let max_count = counts.iter().copied().max().unwrap_or(0).max(1);
+    let style = Style {
+        flex_direction: FlexDirection::Row,
+        align_items: AlignItems::FlexEnd,
+        ..Default::default()
+    };
+    parent
+        .spawn_bundle(chart_bundle(materials, style))
+        .with_children(|parent| {
+            for (bin, &count) in counts.iter().enumerate() {
+                let material = match bin.cmp(&(HISTOGRAM_BINS / 2)) {
+                    std::cmp::Ordering::Less => materials.early.clone(),
+                    std::cmp::Ordering::Equal => materials.center.clone(),
+                    std::cmp::Ordering::Greater => materials.late.clone(),
+                };
+                parent.spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(
+                            Val::Px(CHART_WIDTH / HISTOGRAM_BINS as f32 - 2.0),
+                            Val::Percent(count as f32 / max_count as f32 * 100.0),
+                        ),
+                        margin: Rect {
+                            left: Val::Px(1.0),
+                            right: Val::Px(1.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    material,
+                    ..Default::default()
+                });
+            }
+        });
+}
+
+/// 横轴为歌曲时间，纵轴为误差，上方是延后
+fn spawn_offset_graph(
+    parent: &mut ChildBuilder,
+    offsets: &[HitOffset],
+    materials: &TimingMaterials,
+) {
+    let start = offsets.first().map_or(0.0, |hit| hit.time);
+    let end = offsets.last().map_or(0.0, |hit| hit.time);
+    let duration = (end - start).max(f64::EPSILON);
+    let point = |left: f32, bottom: f32, size: Size<Val>| Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+            left: Val::Percent(left),
+            bottom: Val::Percent(bottom),
+            ..Default::default()
+        },
+        size,
+        ..Default::default()
+    };
+    parent
+        .spawn_bundle(chart_bundle(materials, Style::default()))
+        .with_children(|parent| {
+            // 零误差
+            parent.spawn_bundle(NodeBundle {
+                style: point(0.0, 50.0, Size::new(Val::Percent(100.0), Val::Px(1.0))),
+                material: materials.center.clone(),
+                ..Default::default()
+            });
+            for hit in offsets {
+                let offset_ms = hit.offset * 1000.0;
+                let x = ((hit.time - start) / duration * 100.0) as f32;
+                let y = (offset_ms / MAX_OFFSET_MS).max(-1.0).min(1.0) * 50.0 + 50.0;
+                parent.spawn_bundle(NodeBundle {
+                    style: point(
+                        x.min(99.0),
+                        y.min(98.0),
+                        Size::new(Val::Px(3.0), Val::Px(3.0)),
+                    ),
+                    material: materials.offset(offset_ms),
+                    ..Default::default()
+                });
+            }
+        });
+}
@@ -0,0 +1,113 @@
+use std::cmp::Ordering;
+
+use super::*;
+
+/// 分数高的获胜，分数相同时比较准确率，都相同时平局
+fn winner(one: &ScoreResource, two: &ScoreResource) -> Option<Player> {
+    let order = one.score().cmp(&two.score()).then_with(|| {
+        one.accuracy()
+            .partial_cmp(&two.accuracy())
+            .unwrap_or(Ordering::Equal)
+    });
+    match order {
+        Ordering::Greater => Some(Player::One),
+        Ordering::Less => Some(Player::Two),
+        Ordering::Equal => None,
+    }
+}
+
+/// 一个玩家的成绩
+fn player_lines(player: Player, score: &ScoreResource) -> [(String, f32); 5] {
+    [
+        (player.name().to_string(), 36.0),
+        (score.grade().name().to_string(), 60.0),
+        (format!("Score: {:07}", score.score()), 26.0),
+        (format!("Accuracy: {:.2}%", score.accuracy()), 26.0),
+        (
+            format!(
+                "Perfect: {}  Great: {}\nGood: {}  Miss: {}",
+                score.perfects(),
+                score.greats(),
+                score.goods(),
+                score.fails()
+            ),
+            18.0,
+        ),
+    ]
+}
+
+/// 双人对战的成绩：上面是胜负，下面左右分别是 1P 和 2P 的成绩
+pub(super) fn spawn_versus_results(
+    cmd: &mut Commands,
+    result: &PlayResult,
+    rival: &ScoreResource,
+    font: Handle<Font>,
+    none: Handle<ColorMaterial>,
+) {
+    let text = |value: String, font_size: f32, color: Color| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(5.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font: font.clone(),
+                font_size,
+                color,
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    };
+    let node = |flex_direction: FlexDirection| NodeBundle {
+        style: Style {
+            flex_direction,
+            justify_content: JustifyContent::SpaceAround,
+            align_items: AlignItems::Center,
+            margin: Rect::all(Val::Px(10.0)),
+            ..Default::default()
+        },
+        material: none.clone(),
+        ..Default::default()
+    };
+    let winner = winner(&result.score, rival);
+    let headline = match winner {
+        Some(player) => format!("{} wins!", player.name()),
+        None => String::from("Draw"),
+    };
+    let white = Color::rgb(0.9, 0.9, 0.9);
+    let gold = Color::rgb(1.0, 0.85, 0.3);
+    cmd.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: none.clone(),
+        ..Default::default()
+    })
+    .insert(ResultsUI)
+    .with_children(|parent| {
+        parent.spawn_bundle(text(result.name.clone(), 36.0, white));
+        parent.spawn_bundle(text(headline, 60.0, gold));
+        parent
+            .spawn_bundle(node(FlexDirection::Row))
+            .with_children(|parent| {
+                for (player, score) in [(Player::One, &result.score), (Player::Two, rival)] {
+                    let color = if winner == Some(player) { gold } else { white };
+                    parent
+                        .spawn_bundle(node(FlexDirection::ColumnReverse))
+                        .with_children(|parent| {
+                            for (line, font_size) in player_lines(player, score).iter() {
+                                parent.spawn_bundle(text(line.clone(), *font_size, color));
+                            }
+                        });
+                }
+            });
+        parent.spawn_bundle(text(format!("Mods: {}", result.mods.name()), 18.0, white));
+        parent.spawn_bundle(text(String::from("Press Enter to continue"), 20.0, white));
+    });
+}
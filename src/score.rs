@@ -1,35 +1,311 @@
-use crate::consts::THRESHOLD;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
+use crate::arrows::{CorrectArrowEvent, MissArrowEvent};
+use crate::consts::{DELAY_SONG, THRESHOLD};
+use crate::time::ControlledTime;
+use crate::types::{Player, SongConfig};
+use crate::AppState;
+
+/// 每首歌开始时重置分数，游戏中根据判定计分并记录时间误差
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ScoreResource>()
+            .init_resource::<Player2Score>()
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(reset_score.system()))
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(update_scores.system().label("update_scores").after("judge")),
+            );
+    }
+}
+
+/// 双人对战时 2P 的分数，1P（和单人游戏）使用 ScoreResource
 #[derive(Default)]
+pub struct Player2Score(pub ScoreResource);
+
+/// 根据判定计分，击中时记录时间误差
+fn update_scores(
+    mut correct_event: EventReader<CorrectArrowEvent>,
+    mut miss_event: EventReader<MissArrowEvent>,
+    mut score_1p: ResMut<ScoreResource>,
+    mut score_2p: ResMut<Player2Score>,
+    time: Res<ControlledTime>,
+) {
+    let song_time = time.seconds_since_startup() - DELAY_SONG;
+    for event in correct_event.iter() {
+        let score = match event.player {
+            Player::One => &mut *score_1p,
+            Player::Two => &mut score_2p.0,
+        };
+        score.increase_correct(event.distance);
+        score.offsets.push(HitOffset {
+            time: song_time,
+            offset: event.offset,
+        });
+    }
+    for event in miss_event.iter() {
+        match event.player {
+            Player::One => score_1p.increase_fails(),
+            Player::Two => score_2p.0.increase_fails(),
+        }
+    }
+}
+
+fn reset_score(
+    mut score_1p: ResMut<ScoreResource>,
+    mut score_2p: ResMut<Player2Score>,
+    config: Res<SongConfig>,
+) {
+    *score_1p = ScoreResource::new(config.notes(Player::One));
+    score_2p.0 = ScoreResource::new(config.notes(Player::Two));
+}
+
+/// 判定等级
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Judgement {
+    Perfect,
+    Great,
+    Good,
+}
+
+impl Judgement {
+    /// 根据离目标的距离判定
+    pub fn from_distance(distance: f32) -> Self {
+        let ratio = distance.abs() / THRESHOLD;
+        if ratio <= 0.25 {
+            Judgement::Perfect
+        } else if ratio <= 0.6 {
+            Judgement::Great
+        } else {
+            Judgement::Good
+        }
+    }
+}
+
+/// 满分
+pub const MAX_SCORE: usize = 1_000_000;
+
+/// 评级，按准确率划分，失败时为 F
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Grade {
+    F,
+    D,
+    C,
+    B,
+    A,
+    S,
+    #[serde(rename = "SS")]
+    Ss,
+    #[serde(rename = "SSS")]
+    Sss,
+}
+
+impl Grade {
+    /// 各评级需要的最低准确率，从高到低
+    const THRESHOLDS: [(Grade, f64); 7] = [
+        (Grade::Sss, 99.0),
+        (Grade::Ss, 97.0),
+        (Grade::S, 94.0),
+        (Grade::A, 90.0),
+        (Grade::B, 80.0),
+        (Grade::C, 70.0),
+        (Grade::D, 60.0),
+    ];
+    /// 准确率（0 到 100）对应的评级
+    pub fn from_accuracy(accuracy: f64) -> Self {
+        Self::THRESHOLDS
+            .iter()
+            .find(|(_, threshold)| accuracy >= *threshold)
+            .map_or(Grade::F, |(grade, _)| *grade)
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            Grade::F => "F",
+            Grade::D => "D",
+            Grade::C => "C",
+            Grade::B => "B",
+            Grade::A => "A",
+            Grade::S => "S",
+            Grade::Ss => "SS",
+            Grade::Sss => "SSS",
+        }
+    }
+}
+
+/// 通关标记，越往后越好
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ClearLamp {
+    /// 血条减到 0
+    Failed,
+    Clear,
+    /// 没有错过箭头
+    FullCombo,
+    /// 全部 Perfect
+    AllPerfect,
+}
+
+impl ClearLamp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClearLamp::Failed => "Failed",
+            ClearLamp::Clear => "Clear",
+            ClearLamp::FullCombo => "Full Combo",
+            ClearLamp::AllPerfect => "All Perfect",
+        }
+    }
+}
+
+/// 一次击中的时间误差
+#[derive(Copy, Clone)]
+pub struct HitOffset {
+    /// 击中时的歌曲时间（秒）
+    pub time: f64,
+    /// 击中时间减去箭头到达目标的时间（秒），负数表示提前
+    pub offset: f32,
+}
+
+#[derive(Default, Clone)]
 pub struct ScoreResource {
     corrects: usize,
     fails: usize,
+    /// 各判定的数量
+    perfects: usize,
+    greats: usize,
+    goods: usize,
 
-    score: usize,
+    /// 每个箭头 10 到 100 分的总和
+    points: usize,
+    /// 谱面的箭头总数，用于换算成满分 1,000,000 的分数
+    total_notes: usize,
+    /// 血条减到 0，歌曲提前结束
+    failed: bool,
+    /// 每次击中的时间误差，按时间顺序
+    offsets: Vec<HitOffset>,
 }
 
 impl ScoreResource {
-    /// 增加分数
-    pub fn increase_correct(&mut self, distance: f32) -> usize {
-        self.corrects += 1;
+    pub fn new(total_notes: usize) -> Self {
+        Self {
+            total_notes,
+            ..Default::default()
+        }
+    }
+    /// 击中离目标 distance 的箭头得到的分数
+    pub fn points(distance: f32) -> usize {
         // 根据离目标远近获取分数倍率加成(0到1)
         let score_multiplier = (THRESHOLD - distance.abs()) / THRESHOLD;
         // 分数最低10,最高100
-        let points = (score_multiplier * 100.0).min(100.0).max(10.0) as usize;
-        self.score += points;
+        (score_multiplier * 100.0).min(100.0).max(10.0) as usize
+    }
+    /// 增加分数
+    pub fn increase_correct(&mut self, distance: f32) -> usize {
+        self.corrects += 1;
+        match Judgement::from_distance(distance) {
+            Judgement::Perfect => self.perfects += 1,
+            Judgement::Great => self.greats += 1,
+            Judgement::Good => self.goods += 1,
+        }
+        let points = Self::points(distance);
+        self.points += points;
         points
     }
     /// 统计失败数
     pub fn increase_fails(&mut self) {
         self.fails += 1;
     }
+    /// 失败，歌曲提前结束
+    pub fn fail(&mut self) {
+        self.failed = true;
+    }
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+    /// 满分 1,000,000 的分数：得分占整个谱面满分的比例，和谱面长度无关
+    pub fn score(&self) -> usize {
+        if self.total_notes == 0 {
+            return 0;
+        }
+        self.points * MAX_SCORE / (self.total_notes * 100)
+    }
+    pub fn perfects(&self) -> usize {
+        self.perfects
+    }
+    pub fn greats(&self) -> usize {
+        self.greats
+    }
+    pub fn goods(&self) -> usize {
+        self.goods
+    }
+    pub fn fails(&self) -> usize {
+        self.fails
+    }
+    /// 加权准确率（0 到 100）：Perfect 100%，Great 70%，Good 40%，错过 0，只计算已判定的箭头
+    pub fn accuracy(&self) -> f64 {
+        let judged = self.corrects + self.fails;
+        if judged == 0 {
+            return 100.0;
+        }
+        let weighted = self.perfects as f64 + self.greats as f64 * 0.7 + self.goods as f64 * 0.4;
+        weighted / judged as f64 * 100.0
+    }
+    pub fn offsets(&self) -> &[HitOffset] {
+        &self.offsets
+    }
+    /// 平均误差（毫秒），负数表示整体偏早，还没有击中时为 None
+    pub fn mean_offset_ms(&self) -> Option<f32> {
+        if self.offsets.is_empty() {
+            return None;
+        }
+        let sum: f64 = self.offsets.iter().map(|hit| hit.offset as f64).sum();
+        Some((sum / self.offsets.len() as f64 * 1000.0) as f32)
+    }
+    /// 误差的标准差（毫秒）
+    pub fn offset_std_dev_ms(&self) -> Option<f32> {
+        let mean = self.mean_offset_ms()? as f64;
+        let variance: f64 = self
+            .offsets
+            .iter()
+            .map(|hit| (hit.offset as f64 * 1000.0 - mean).powi(2))
+            .sum::<f64>()
+            / self.offsets.len() as f64;
+        Some(variance.sqrt() as f32)
+    }
+    /// 不稳定度：标准差（毫秒）的 10 倍，越小越稳
+    pub fn unstable_rate(&self) -> Option<f32> {
+        self.offset_std_dev_ms().map(|std_dev| std_dev * 10.0)
+    }
+    pub fn grade(&self) -> Grade {
+        if self.failed {
+            Grade::F
+        } else {
+            Grade::from_accuracy(self.accuracy())
+        }
+    }
+    /// 通关标记，游戏中表示目前为止的情况
+    pub fn lamp(&self) -> ClearLamp {
+        if self.failed {
+            ClearLamp::Failed
+        } else if self.fails > 0 {
+            ClearLamp::Clear
+        } else if self.greats + self.goods > 0 {
+            ClearLamp::FullCombo
+        } else {
+            ClearLamp::AllPerfect
+        }
+    }
 }
 impl std::fmt::Display for ScoreResource {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "Score: {}. Corrects: {}. Fails: {}.",
-            self.score, self.corrects, self.fails
+            "Score: {:07}  {:.2}%  {}  {}",
+            self.score(),
+            self.accuracy(),
+            self.grade().name(),
+            self.lamp().name()
         )
     }
 }
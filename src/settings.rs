@@ -0,0 +1,140 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::audio::{AudioChannel, AudioPlayer};
+use crate::gauge::GaugeType;
+use crate::mods::Mods;
+use crate::AppState;
+
+/// 设置文件
+const SETTINGS_FILE: &str = "settings.toml";
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Settings::load())
+            .init_resource::<UnsavedSettings>()
+            .add_system(apply_settings.system())
+            .add_system_set(
+                SystemSet::on_exit(AppState::Settings).with_system(save_settings.system()),
+            )
+            // 在关闭窗口发出 AppExit 的同一帧保存
+            .add_system_to_stage(CoreStage::Last, save_settings_on_exit.system());
+    }
+}
+
+/// 玩家设置，保存在 settings.toml
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub volume: VolumeSettings,
+    pub gameplay: GameplaySettings,
+}
+
+/// 游戏选项，在菜单中选择
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameplaySettings {
+    pub gauge: GaugeType,
+    pub mods: Mods,
+}
+
+/// 音量（0 到 1）
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VolumeSettings {
+    pub master: f32,
+    pub music: f32,
+    /// 打击音效和按键音
+    pub effects: f32,
+    /// 菜单中的声音
+    pub ui: f32,
+}
+
+impl Default for VolumeSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            effects: 1.0,
+            ui: 1.0,
+        }
+    }
+}
+
+impl VolumeSettings {
+    /// 通道的最终音量
+    pub fn channel(&self, channel: AudioChannel) -> f32 {
+        let volume = match channel {
+            AudioChannel::Music => self.music,
+            AudioChannel::Effects => self.effects,
+            AudioChannel::Preview => self.ui,
+        };
+        self.master * volume
+    }
+}
+
+impl Settings {
+    /// 读取设置，文件不存在或格式错误时使用默认设置
+    pub fn load() -> Self {
+        let contents = match std::fs::read_to_string(SETTINGS_FILE) {
+            Ok(contents) => contents,
+            Err(_) => return Self::default(),
+        };
+        toml::from_str(&contents).unwrap_or_else(|e| {
+            eprintln!("couldn't parse {}: {}", SETTINGS_FILE, e);
+            Self::default()
+        })
+    }
+    pub fn save(&self) {
+        let toml_text = toml::to_string_pretty(self).expect("couldn't convert to toml text");
+        if let Err(e) = std::fs::write(SETTINGS_FILE, toml_text) {
+            eprintln!("couldn't write {}: {}", SETTINGS_FILE, e);
+        }
+    }
+}
+
+/// 设置是否有还没保存的修改
+#[derive(Default)]
+struct UnsavedSettings(bool);
+
+/// 设置变化后应用音量，离开设置界面或者退出时再保存
+fn apply_settings(
+    settings: Res<Settings>,
+    mut unsaved: ResMut<UnsavedSettings>,
+    mut player: ResMut<AudioPlayer>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    for channel in [
+        AudioChannel::Music,
+        AudioChannel::Effects,
+        AudioChannel::Preview,
+    ] {
+        player.set_channel_volume(channel, settings.volume.channel(channel));
+    }
+    // 刚读取的设置不用写回
+    if !settings.is_added() {
+        unsaved.0 = true;
+    }
+}
+
+fn save_settings(settings: Res<Settings>, mut unsaved: ResMut<UnsavedSettings>) {
+    if unsaved.0 {
+        settings.save();
+        unsaved.0 = false;
+    }
+}
+
+fn save_settings_on_exit(
+    mut exit_events: EventReader<AppExit>,
+    settings: Res<Settings>,
+    unsaved: ResMut<UnsavedSettings>,
+) {
+    if exit_events.iter().next().is_some() {
+        save_settings(settings, unsaved);
+    }
+}
@@ -1,11 +1,12 @@
-use crate::consts::TARGET_POSITION;
-use crate::types::Directions;
+use crate::consts::{TARGET_POSITION, VERSUS_LANE_SCALE};
+use crate::types::{Directions, Player, SongConfig};
 
 use super::*;
 use crate::arrows::CorrectArrowEvent;
 
 pub struct TargetArrowSparkle {
     direction: Directions,
+    player: Player,
 }
 
 #[derive(RenderResources)]
@@ -20,6 +21,7 @@ pub fn setup_target_arrows(
     mut shaders: ResMut<Assets<Shader>>,
     mut render_graph: ResMut<RenderGraph>,
     window: Res<WindowDescriptor>,
+    config: Res<SongConfig>,
 ) {
     // 创建着色器管道
     let pipeline_handle = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
@@ -43,16 +45,30 @@ pub fn setup_target_arrows(
         .unwrap();
     use Directions::*;
     let directions = [Up, Down, Left, Right];
-    directions.iter().for_each(|direction| {
+    // 双人对战时每个玩家一组，和箭头一起缩小
+    let (players, scale): (&[Player], f32) = if config.versus {
+        (&Player::ALL, 300. * VERSUS_LANE_SCALE)
+    } else {
+        (&[Player::One], 300.)
+    };
+    let lanes = players.iter().flat_map(|player| {
+        directions
+            .iter()
+            .map(move |direction| (*player, *direction))
+    });
+    lanes.for_each(|(player, direction)| {
         let z = match direction {
             Up => 0.3,
             Down => 0.4,
             Left => 0.5,
             Right => 0.6,
         };
-        let mut transform =
-            Transform::from_translation(Vec3::new(TARGET_POSITION, direction.y(), z));
-        transform.scale = Vec3::new(300., 300., 1.);
+        let mut transform = Transform::from_translation(Vec3::new(
+            TARGET_POSITION,
+            player.lane_y(direction, config.versus),
+            z,
+        ));
+        transform.scale = Vec3::new(scale, scale, 1.);
         // 渲染管道
         let render_pipelines =
             RenderPipelines::from_pipelines(vec![RenderPipeline::new(pipeline_handle.clone())]);
@@ -61,9 +77,7 @@ pub fn setup_target_arrows(
             transform,
             ..Default::default()
         })
-        .insert(TargetArrowSparkle {
-            direction: *direction,
-        })
+        .insert(TargetArrowSparkle { direction, player })
         .insert(TimeSinceLastCorrect {
             last_time: -1.0,
             points: 0.0,
@@ -82,7 +96,7 @@ pub fn correct_event(
 ) {
     correct_event.iter().for_each(|e| {
         q.for_each_mut(|(tas, mut lc)| {
-            if e.direction == tas.direction {
+            if e.direction == tas.direction && e.player == tas.player {
                 lc.last_time = time.seconds_since_startup() as f32;
                 lc.points = e.points as f32 / 100.0;
             }
@@ -0,0 +1,274 @@
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use serde::{Deserialize, Serialize};
+
+use crate::arrows::{CorrectArrowEvent, MissArrowEvent};
+use crate::consts::DELAY_SONG;
+use crate::score::{Judgement, Player2Score, ScoreResource};
+use crate::time::ControlledTime;
+use crate::types::{Directions, Player, SongConfig};
+use crate::{AppState, GameLogicPlugins};
+
+/// 默认的模拟帧率
+pub const DEFAULT_FRAME_RATE: f64 = 60.0;
+/// 最后一个箭头之后最多再模拟这么久（秒），防止歌曲一直不结束
+const TAIL: f64 = 10.0;
+
+/// 脚本中的一次按键
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptedPress {
+    /// 谱面时间（秒），和箭头的点击时间相同
+    pub time: f64,
+    pub direction: Directions,
+    /// 双人对战时按键的玩家，单人游戏都是 1P
+    #[serde(default)]
+    pub player: Player,
+}
+
+/// 按键脚本文件
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct InputScript {
+    pub presses: Vec<ScriptedPress>,
+}
+
+impl InputScript {
+    /// 解析按键脚本，path 只用于错误信息
+    pub fn parse(path: &str, contents: &str) -> Result<Self, String> {
+        toml::from_str(contents).map_err(|e| format!("couldn't parse {}: {}", path, e))
+    }
+    /// 在每个箭头的点击时间之后 offset 秒按下，offset 为负数时提前
+    pub fn autoplay(config: &SongConfig, offset: f64) -> Self {
+        let presses = config
+            .arrows
+            .iter()
+            .map(|arrow| ScriptedPress {
+                time: arrow.click_time() + offset,
+                direction: arrow.direction,
+                player: arrow.player,
+            })
+            .collect();
+        Self { presses }
+    }
+}
+
+/// 一次判定
+#[derive(Debug, Clone)]
+pub struct JudgementRecord {
+    /// 判定时的谱面时间（秒）
+    pub time: f64,
+    pub player: Player,
+    pub direction: Directions,
+    /// None 表示错过
+    pub judgement: Option<Judgement>,
+    /// 击中时间减去箭头到达目标的时间（秒），错过时为 None
+    pub offset: Option<f32>,
+    /// 得分（10 到 100），错过时为 0
+    pub points: usize,
+}
+
+/// 模拟的结果
+pub struct SimulationResult {
+    /// 按时间顺序的所有判定，同一帧的判定按玩家和方向排序
+    pub judgements: Vec<JudgementRecord>,
+    /// 1P（和单人游戏）的分数
+    pub score: ScoreResource,
+    /// 双人对战时 2P 的分数
+    pub rival: Option<ScoreResource>,
+}
+
+/// 模拟中记录的判定
+#[derive(Default)]
+struct JudgementLog(Vec<JudgementRecord>);
+
+/// 不打开窗口、不使用 GPU 和音频设备运行谱面：时间按固定帧率手动步进，
+/// 按脚本中的时间按下按键，返回判定和分数
+pub fn simulate(config: SongConfig, script: &InputScript, frame_rate: f64) -> SimulationResult {
+    let last_click = config
+        .arrows
+        .iter()
+        .map(|arrow| arrow.click_time())
+        .fold(0.0, f64::max);
+    let versus = config.versus;
+    let mut builder = App::build();
+    builder
+        .add_plugins(MinimalPlugins)
+        .add_state(AppState::Game)
+        .add_plugins(GameLogicPlugins)
+        .insert_resource(ControlledTime::manual())
+        .insert_resource(Input::<KeyCode>::default())
+        .insert_resource(config)
+        .init_resource::<JudgementLog>()
+        .add_system_set(
+            SystemSet::on_update(AppState::Game)
+                .with_system(record_judgements.system().after("judge")),
+        );
+    let mut app = builder.app;
+
+    let mut presses = script.presses.clone();
+    presses.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    let mut presses = presses.into_iter().peekable();
+    let frame = Duration::from_secs_f64(1.0 / frame_rate);
+    let mut elapsed = Duration::from_secs(0);
+    loop {
+        let time = elapsed.as_secs_f64() - DELAY_SONG;
+        {
+            let mut input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+            // 上一帧按下的键在这一帧松开
+            let pressed: Vec<KeyCode> = input.get_pressed().copied().collect();
+            input.update();
+            for key in pressed {
+                input.release(key);
+            }
+            // 在离按键时间最近的一帧按下
+            while let Some(press) = presses.next_if(|press| press.time <= time + 0.5 / frame_rate) {
+                // 单人游戏时 1P 的按键也可以使用
+                input.press(press.player.key(press.direction));
+            }
+        }
+        app.update();
+        let finished = app.world.get_resource::<SongConfig>().unwrap().finished;
+        if finished || time > last_click + TAIL {
+            break;
+        }
+        elapsed += frame;
+        app.world
+            .get_resource_mut::<ControlledTime>()
+            .unwrap()
+            .advance(frame);
+    }
+
+    let world = &mut app.world;
+    // 同一帧中箭头的判定顺序不固定
+    let mut judgements = world.remove_resource::<JudgementLog>().unwrap().0;
+    judgements.sort_by(|a, b| {
+        let key = |record: &JudgementRecord| (record.player as usize, record.direction.index());
+        a.time
+            .partial_cmp(&b.time)
+            .unwrap()
+            .then_with(|| key(a).cmp(&key(b)))
+    });
+    SimulationResult {
+        judgements,
+        score: world.remove_resource::<ScoreResource>().unwrap(),
+        rival: if versus {
+            world.remove_resource::<Player2Score>().map(|score| score.0)
+        } else {
+            None
+        },
+    }
+}
+
+fn record_judgements(
+    mut log: ResMut<JudgementLog>,
+    time: Res<ControlledTime>,
+    mut correct_event: EventReader<CorrectArrowEvent>,
+    mut miss_event: EventReader<MissArrowEvent>,
+) {
+    let time = time.seconds_since_startup() - DELAY_SONG;
+    for event in correct_event.iter() {
+        log.0.push(JudgementRecord {
+            time,
+            player: event.player,
+            direction: event.direction,
+            judgement: Some(event.judgement),
+            offset: Some(event.offset),
+            points: event.points,
+        });
+    }
+    for event in miss_event.iter() {
+        log.0.push(JudgementRecord {
+            time,
+            player: event.player,
+            direction: event.direction,
+            judgement: None,
+            offset: None,
+            points: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::score::{ClearLamp, MAX_SCORE};
+    use crate::types::{SongConfigToml, SongSounds};
+
+    /// 测试用的谱面，不加载音频
+    fn test_chart() -> SongConfig {
+        let path = "assets/songs/test.toml";
+        let chart = SongConfigToml::parse(path, include_str!("../assets/songs/test.toml")).unwrap();
+        SongConfig::new(
+            chart.name.clone(),
+            Default::default(),
+            &chart.arrows,
+            SongSounds::default(),
+        )
+    }
+
+    fn run(offset: f64, frame_rate: f64) -> (usize, SimulationResult) {
+        let config = test_chart();
+        let notes = config.arrows.len();
+        let script = InputScript::autoplay(&config, offset);
+        (notes, simulate(config, &script, frame_rate))
+    }
+
+    #[test]
+    fn autoplay_is_all_perfect() {
+        let (notes, result) = run(0.0, DEFAULT_FRAME_RATE);
+        assert_eq!(result.judgements.len(), notes);
+        assert!(result
+            .judgements
+            .iter()
+            .all(|record| record.judgement == Some(Judgement::Perfect)));
+        assert_eq!(result.score.perfects(), notes);
+        assert_eq!(result.score.fails(), 0);
+        assert_eq!(result.score.lamp(), ClearLamp::AllPerfect);
+        assert!(result.rival.is_none());
+    }
+
+    #[test]
+    fn late_presses_are_good() {
+        // 晚 4 帧：超出 Great 的范围但还能击中
+        let (notes, result) = run(4.0 / DEFAULT_FRAME_RATE, DEFAULT_FRAME_RATE);
+        assert_eq!(result.judgements.len(), notes);
+        assert!(result
+            .judgements
+            .iter()
+            .all(|record| record.judgement == Some(Judgement::Good)));
+        assert_eq!(result.score.goods(), notes);
+        assert_eq!(result.score.lamp(), ClearLamp::FullCombo);
+    }
+
+    #[test]
+    fn presses_far_from_the_arrows_miss() {
+        for offset in [-0.2, 0.2].iter() {
+            let (notes, result) = run(*offset, DEFAULT_FRAME_RATE);
+            assert_eq!(result.judgements.len(), notes);
+            assert!(result
+                .judgements
+                .iter()
+                .all(|record| record.judgement.is_none() && record.points == 0));
+            assert_eq!(result.score.fails(), notes);
+            assert_eq!(result.score.score(), 0);
+        }
+    }
+
+    #[test]
+    fn judgements_add_up_to_the_score() {
+        let (notes, result) = run(-0.03, 30.0);
+        let count = |judgement| {
+            result
+                .judgements
+                .iter()
+                .filter(|record| record.judgement == judgement)
+                .count()
+        };
+        let score = &result.score;
+        assert_eq!(count(Some(Judgement::Perfect)), score.perfects());
+        assert_eq!(count(Some(Judgement::Great)), score.greats());
+        assert_eq!(count(Some(Judgement::Good)), score.goods());
+        assert_eq!(count(None), score.fails());
+        let points: usize = result.judgements.iter().map(|record| record.points).sum();
+        assert_eq!(points * MAX_SCORE / (notes * 100), score.score());
+    }
+}
@@ -8,15 +8,20 @@ pub struct TimePlugin;
 impl Plugin for TimePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ControlledTime>()
+            .init_resource::<GameStartTime>()
+            .init_resource::<GameRate>()
             .add_system_set(
                 SystemSet::on_enter(AppState::Game)
                     .with_system(reset_time_when_enter_game.system()),
             )
             .add_system_set(
                 SystemSet::on_enter(AppState::MakeMap)
-                    .with_system(reset_time_when_enter_game.system()),
+                    .with_system(reset_time_when_enter_map_maker.system().label("reset_time")),
+            )
+            .add_system_set(
+                SystemSet::on_update(AppState::Game)
+                    .with_system(update_time.system().label("update_time")),
             )
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(update_time.system()))
             .add_system_set(
                 SystemSet::on_update(AppState::MakeMap).with_system(update_time.system()),
             );
@@ -30,6 +35,11 @@ pub struct ControlledTime {
     delta_seconds: f32,
     seconds_since_startup: f64,
     startup: Instant,
+    paused: bool,
+    /// 时间流逝的速度，1 为正常速度
+    rate: f64,
+    /// 手动步进的时钟，模拟时使用，为 None 时使用系统时间
+    clock: Option<Instant>,
 }
 
 impl Default for ControlledTime {
@@ -41,28 +51,95 @@ impl Default for ControlledTime {
             delta_seconds: 0.0,
             seconds_since_startup: 0.0,
             startup: Instant::now(),
+            paused: false,
+            rate: 1.0,
+            clock: None,
         }
     }
 }
 
+/// 进入游戏时的起始时间（秒），从编辑器试玩时不为 0
+#[derive(Default)]
+pub struct GameStartTime(pub f64);
+
+/// 游戏中时间流逝的速度，练习模式中可以变慢或变快
+pub struct GameRate(pub f64);
+
+impl Default for GameRate {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
 impl ControlledTime {
+    /// 只在调用 advance 时前进的时间
+    pub fn manual() -> Self {
+        Self {
+            clock: Some(Instant::now()),
+            ..Default::default()
+        }
+    }
+    /// 手动步进的时钟前进 delta，下一次 update 时生效
+    pub fn advance(&mut self, delta: Duration) {
+        if let Some(clock) = &mut self.clock {
+            *clock += delta;
+        }
+    }
+    fn now(&self) -> Instant {
+        self.clock.unwrap_or_else(Instant::now)
+    }
     pub fn reset_time(&mut self) {
-        self.startup = Instant::now();
-        self.seconds_since_startup = 0.0;
+        self.reset_time_at(0.0);
+    }
+    /// 重置时间，并从 seconds 开始计时
+    pub fn reset_time_at(&mut self, seconds: f64) {
+        self.paused = false;
+        self.seek(seconds);
+    }
+    /// 跳转到 seconds，保持暂停状态
+    pub fn seek(&mut self, seconds: f64) {
+        let now = self.now();
+        self.startup = now - Duration::from_secs_f64(seconds.max(0.0) / self.rate);
+        self.seconds_since_startup = seconds.max(0.0);
+        self.last_update = None;
+    }
+    /// 暂停时时间不前进
+    pub fn set_paused(&mut self, paused: bool) {
+        if self.paused && !paused {
+            // 从暂停的位置继续
+            self.seek(self.seconds_since_startup);
+        }
+        self.paused = paused;
+    }
+
+    /// 改变时间流逝的速度，从当前时间继续
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+        self.seek(self.seconds_since_startup);
+    }
+    #[inline]
+    pub fn rate(&self) -> f64 {
+        self.rate
     }
 
     pub fn update(&mut self) {
-        self.update_with_instant(Instant::now());
+        self.update_with_instant(self.now());
     }
 
     pub fn update_with_instant(&mut self, instant: Instant) {
+        if self.paused {
+            self.delta = Duration::from_secs(0);
+            self.delta_seconds = 0.0;
+            self.delta_seconds_f64 = 0.0;
+            return;
+        }
         if let Some(last_update) = self.last_update {
-            self.delta = instant - last_update;
+            self.delta = (instant - last_update).mul_f64(self.rate);
             self.delta_seconds = self.delta.as_secs_f32();
             self.delta_seconds_f64 = self.delta.as_secs_f64();
         }
         let duration_since_startup = instant - self.startup;
-        self.seconds_since_startup = duration_since_startup.as_secs_f64();
+        self.seconds_since_startup = duration_since_startup.as_secs_f64() * self.rate;
         self.last_update = Some(instant);
     }
 
@@ -84,6 +161,15 @@ impl ControlledTime {
 pub fn update_time(mut time: ResMut<ControlledTime>) {
     time.update();
 }
-pub fn reset_time_when_enter_game(mut time: ResMut<ControlledTime>) {
+pub fn reset_time_when_enter_game(
+    mut time: ResMut<ControlledTime>,
+    start: Res<GameStartTime>,
+    rate: Res<GameRate>,
+) {
+    time.set_rate(rate.0);
+    time.reset_time_at(start.0);
+}
+pub fn reset_time_when_enter_map_maker(mut time: ResMut<ControlledTime>) {
+    time.set_rate(1.0);
     time.reset_time();
 }
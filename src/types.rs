@@ -1,9 +1,16 @@
+use std::collections::{BTreeMap, HashMap};
 use std::f32::consts::{FRAC_PI_2, PI};
+use std::path::Path;
 
+use bevy::asset::HandleId;
 use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
 use serde::{Deserialize, Serialize};
 
+use crate::audio::normalization_gain;
 use crate::consts::*;
+use crate::mods::Mods;
+use crate::score::Judgement;
 
 /// 箭头方向
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
@@ -45,6 +52,16 @@ impl Directions {
         }
     }
 
+    /// 在 directions() 中的位置
+    pub fn index(&self) -> usize {
+        match self {
+            Directions::Up => 0,
+            Directions::Down => 1,
+            Directions::Left => 2,
+            Directions::Right => 3,
+        }
+    }
+
     pub const fn directions() -> [Directions; 4] {
         [
             Directions::Up,
@@ -55,6 +72,68 @@ impl Directions {
     }
 }
 
+/// 玩家，单人游戏只有 1P
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Player {
+    One,
+    Two,
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Player::One
+    }
+}
+
+impl Player {
+    pub const ALL: [Player; 2] = [Player::One, Player::Two];
+    pub fn name(&self) -> &'static str {
+        match self {
+            Player::One => "1P",
+            Player::Two => "2P",
+        }
+    }
+    /// 双人对战时的按键：1P 使用 WASD，2P 使用方向键
+    pub fn key(&self, direction: Directions) -> KeyCode {
+        match (self, direction) {
+            (Player::One, Directions::Up) => KeyCode::W,
+            (Player::One, Directions::Down) => KeyCode::S,
+            (Player::One, Directions::Left) => KeyCode::A,
+            (Player::One, Directions::Right) => KeyCode::D,
+            (Player::Two, Directions::Up) => KeyCode::Up,
+            (Player::Two, Directions::Down) => KeyCode::Down,
+            (Player::Two, Directions::Left) => KeyCode::Left,
+            (Player::Two, Directions::Right) => KeyCode::Right,
+        }
+    }
+    /// 这个玩家的方向键是否被按下，单人游戏时两组按键都可以
+    pub fn key_just_pressed(
+        &self,
+        direction: Directions,
+        input: &Input<KeyCode>,
+        versus: bool,
+    ) -> bool {
+        if versus {
+            input.just_pressed(self.key(direction))
+        } else {
+            direction.key_just_pressed(input)
+        }
+    }
+    /// 跑道的纵坐标，双人对战时 1P 在上半边，2P 在下半边
+    /// 箭头从左向右移动，左右分开会让每个玩家的跑道只剩半个屏幕宽，
+    /// 箭头出现得更晚，所以两组跑道上下并排，移动距离和单人游戏相同
+    pub fn lane_y(&self, direction: Directions, versus: bool) -> f32 {
+        if !versus {
+            return direction.y();
+        }
+        let center = match self {
+            Player::One => WINDOW_HEIGHT / 4.0,
+            Player::Two => -WINDOW_HEIGHT / 4.0,
+        };
+        center + direction.y() * VERSUS_LANE_SCALE
+    }
+}
+
 /// 箭头速度
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub enum Speed {
@@ -76,19 +155,33 @@ impl Speed {
             Speed::Fast => 1.5,
         }
     }
+    /// 下一档速度（循环切换）
+    pub fn next(&self) -> Speed {
+        match self {
+            Speed::Slow => Speed::Medium,
+            Speed::Medium => Speed::Fast,
+            Speed::Fast => Speed::Slow,
+        }
+    }
 }
 
 /// 每个箭头的基本属性
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub struct ArrowTime {
     pub spawn_time: f64,
     pub speed: Speed,
     pub direction: Directions,
+    /// 击中时播放的按键音
+    pub keysound: Option<Handle<AudioSource>>,
+    /// 高速倍数，只改变移动速度和生成时间
+    pub hi_speed: f32,
+    /// 双人对战时箭头属于哪个玩家
+    pub player: Player,
 }
 
 impl ArrowTime {
     /// click_time 是按钮点击时间
-    pub fn new(arrow: &ArrowTimeToml) -> Self {
+    pub fn new(arrow: &ArrowTimeToml, keysounds: &HashMap<String, Handle<AudioSource>>) -> Self {
         let speed_value = arrow.speed.value();
         // 根据点击时间计算出生成箭头时间
         let spawn_time = arrow.click_time - (DISTANCE / speed_value) as f64;
@@ -96,52 +189,311 @@ impl ArrowTime {
             spawn_time,
             speed: arrow.speed,
             direction: arrow.direction,
+            keysound: arrow
+                .keysound
+                .as_ref()
+                .and_then(|name| keysounds.get(name).cloned()),
+            hi_speed: 1.0,
+            player: Player::One,
         }
     }
+    /// 实际的移动速度
+    pub fn velocity(&self) -> f32 {
+        self.speed.value() * self.hi_speed
+    }
+    /// 按钮点击时间
+    pub fn click_time(&self) -> f64 {
+        self.spawn_time + (DISTANCE / self.velocity()) as f64
+    }
+    /// 改变高速，点击时间不变
+    pub fn set_hi_speed(&mut self, hi_speed: f32) {
+        let click_time = self.click_time();
+        self.hi_speed = hi_speed;
+        self.spawn_time = click_time - (DISTANCE / self.velocity()) as f64;
+    }
 }
 
 /// 一首歌的 箭头序列 配置
+#[derive(Clone)]
 pub struct SongConfig {
     pub name: String,
+    /// 谱面文件（相对于 assets/songs）和谱面资源，试玩时为 None
+    pub chart_file: Option<String>,
+    pub chart: Option<Handle<SongConfigToml>>,
+    /// 谱面内容的哈希，成绩按哈希记录，试玩时为 None
+    pub chart_hash: Option<String>,
     pub song_audio: Handle<AudioSource>,
+    /// 歌曲音量（响度统一的增益）
+    pub volume: f32,
+    pub hit_sounds: HitSounds,
     pub arrows: Vec<ArrowTime>,
+    /// 生成谱面时应用的 mod
+    pub mods: Mods,
+    /// 双人对战，两个玩家的箭头都在 arrows 中
+    pub versus: bool,
+    /// 所有箭头都已击中或错过
+    pub finished: bool,
 }
 
 impl SongConfig {
-    pub fn load_config(path: &str, asset_server: &AssetServer) -> Self {
-        // 读取文件
-        let contents =
-            std::fs::read_to_string(format!("assets/songs/{}", path)).expect("couldn't read file");
-        // 解析文件
-        let parsed: SongConfigToml = toml::from_str(&contents).expect("parse file error");
+    /// 由加载的谱面生成配置并应用 mod，chart_file 相对于 assets/songs
+    pub fn from_chart(
+        chart: &SongConfigToml,
+        chart_file: String,
+        handle: Handle<SongConfigToml>,
+        asset_server: &AssetServer,
+        mods: Mods,
+    ) -> Self {
+        // 加载音频文件
+        let song_audio = asset_server.load(&*format!(
+            "songs/{}",
+            chart_asset_path(&chart_file, &chart.filename)
+        ));
+        let sounds = SongSounds::load(
+            &chart.hit_sounds,
+            &chart.keysounds,
+            &chart_file,
+            asset_server,
+        );
+        let mut config = Self::new(chart.name.clone(), song_audio, &chart.arrows, sounds);
+        config.volume = normalization_gain(chart.loudness);
+        config.chart_file = Some(chart_file);
+        config.chart = Some(handle);
+        config.chart_hash = Some(chart.hash.clone());
+        mods.apply(&mut config.arrows);
+        config.mods = mods;
+        config
+    }
+    /// 由箭头点击时间序列生成配置
+    pub fn new(
+        name: String,
+        song_audio: Handle<AudioSource>,
+        arrows: &[ArrowTimeToml],
+        sounds: SongSounds,
+    ) -> Self {
         // 处理解析文件
-        let mut arrows: Vec<ArrowTime> = parsed
-            .arrows
+        let mut arrows: Vec<ArrowTime> = arrows
             .iter()
-            .map(|arr| ArrowTime::new(arr))
+            .map(|arr| ArrowTime::new(arr, &sounds.keysounds))
             .collect();
         // 排序
         arrows.sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
-        // 加载音频文件
-        let song_audio = asset_server.load(&*format!("songs/{}", parsed.filename));
         Self {
-            name: parsed.name,
+            name,
+            chart_file: None,
+            chart: None,
+            chart_hash: None,
             song_audio,
+            volume: 1.0,
+            hit_sounds: sounds.hit_sounds,
             arrows,
+            mods: Mods::default(),
+            versus: false,
+            finished: false,
+        }
+    }
+    /// 加入 2P 的谱面开始双人对战，对战不记录成绩也不响应谱面修改
+    pub fn add_rival(&mut self, rival: SongConfig) {
+        self.chart_file = None;
+        self.chart = None;
+        self.chart_hash = None;
+        self.versus = true;
+        self.arrows
+            .extend(rival.arrows.into_iter().map(|arrow| ArrowTime {
+                player: Player::Two,
+                ..arrow
+            }));
+        self.arrows
+            .sort_by(|a, b| a.spawn_time.partial_cmp(&b.spawn_time).unwrap());
+    }
+    /// 这个玩家的箭头数量
+    pub fn notes(&self, player: Player) -> usize {
+        self.arrows
+            .iter()
+            .filter(|arrow| arrow.player == player)
+            .count()
+    }
+}
+
+/// 打击音效
+#[derive(Default, Clone)]
+pub struct HitSounds {
+    pub perfect: Option<Handle<AudioSource>>,
+    pub great: Option<Handle<AudioSource>>,
+    pub good: Option<Handle<AudioSource>>,
+    pub miss: Option<Handle<AudioSource>>,
+}
+
+impl HitSounds {
+    /// 判定对应的音效，judgement 为 None 表示 miss
+    pub fn get(&self, judgement: Option<Judgement>) -> Option<Handle<AudioSource>> {
+        match judgement {
+            Some(Judgement::Perfect) => self.perfect.clone(),
+            Some(Judgement::Great) => self.great.clone(),
+            Some(Judgement::Good) => self.good.clone(),
+            None => self.miss.clone(),
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct SongConfigToml {
+/// 预加载的谱面音效
+#[derive(Default, Clone)]
+pub struct SongSounds {
+    pub hit_sounds: HitSounds,
+    /// 按键音，按名字索引
+    pub keysounds: HashMap<String, Handle<AudioSource>>,
+}
+
+impl SongSounds {
+    /// 开始加载谱面声明的音效，游戏开始前就会加载完成
+    pub fn load(
+        hit_sounds: &HitSoundsToml,
+        keysounds: &BTreeMap<String, String>,
+        chart_file: &str,
+        asset_server: &AssetServer,
+    ) -> Self {
+        let load = |file: &String| {
+            asset_server.load(&*format!("songs/{}", chart_asset_path(chart_file, file)))
+        };
+        Self {
+            hit_sounds: HitSounds {
+                perfect: hit_sounds.perfect.as_ref().map(load),
+                great: hit_sounds.great.as_ref().map(load),
+                good: hit_sounds.good.as_ref().map(load),
+                miss: hit_sounds.miss.as_ref().map(load),
+            },
+            keysounds: keysounds
+                .iter()
+                .map(|(name, file)| (name.clone(), load(file)))
+                .collect(),
+        }
+    }
+    /// 所有音效的资源，用于等待加载完成
+    pub fn handle_ids(&self) -> Vec<HandleId> {
+        let hit_sounds = &self.hit_sounds;
+        [
+            &hit_sounds.perfect,
+            &hit_sounds.great,
+            &hit_sounds.good,
+            &hit_sounds.miss,
+        ]
+        .iter()
+        .filter_map(|sound| sound.as_ref())
+        .chain(self.keysounds.values())
+        .map(|handle| handle.id)
+        .collect()
+    }
+}
+
+/// 谱面引用的文件相对于谱面所在的文件夹，转为相对于 assets/songs 的路径
+pub fn chart_asset_path(chart_file: &str, file: &str) -> String {
+    match Path::new(chart_file).parent() {
+        Some(dir) if dir != Path::new("") => format!("{}/{}", dir.to_string_lossy(), file),
+        _ => file.to_string(),
+    }
+}
+
+/// 谱面文件内容的哈希（FNV-1a），谱面内容不变时保持不变
+pub fn chart_hash(contents: &[u8]) -> String {
+    let hash = contents
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{:016x}", hash)
+}
+
+/// 谱面文件格式，也是 AssetServer 加载的谱面资源
+#[derive(Debug, Default, Clone, Serialize, Deserialize, TypeUuid)]
+#[uuid = "6f1d2b9e-3c4a-4e8b-9a71-2d5c8f0e4b17"]
+pub struct SongConfigToml {
     pub name: String,
+    /// 音频文件，相对于谱面所在的文件夹
     pub filename: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    /// 难度等级（1 到 10）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u32>,
+    /// 每分钟节拍数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f64>,
+    /// 第一拍的时间（秒）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset: Option<f64>,
+    /// 菜单中预览片段的开始时间（秒），没有时使用最响的片段
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview_start: Option<f64>,
+    /// 歌曲响度（dBFS），用于统一不同歌曲的音量
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loudness: Option<f64>,
+    /// 各判定的打击音效
+    #[serde(default, skip_serializing_if = "HitSoundsToml::is_empty")]
+    pub hit_sounds: HitSoundsToml,
+    /// 按键音：名字到音频文件（相对于谱面所在的文件夹）
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub keysounds: BTreeMap<String, String>,
+    /// 段落标记，练习模式中可以选择段落循环
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sections: Vec<SectionToml>,
     pub arrows: Vec<ArrowTimeToml>,
+    /// 谱面文件内容的哈希，解析时计算，不写入谱面
+    #[serde(skip)]
+    pub hash: String,
+}
+
+impl SongConfigToml {
+    /// 解析谱面内容，path 只用于错误信息
+    pub fn parse(path: &str, contents: &str) -> Result<Self, String> {
+        let mut chart: Self =
+            toml::from_str(contents).map_err(|e| format!("couldn't parse {}: {}", path, e))?;
+        chart.hash = chart_hash(contents.as_bytes());
+        Ok(chart)
+    }
+    /// 最后一个箭头的点击时间
+    pub fn last_click_time(&self) -> Option<f64> {
+        self.arrows
+            .iter()
+            .map(|arrow| arrow.click_time)
+            .fold(None, |last: Option<f64>, time| {
+                Some(last.map_or(time, |last| last.max(time)))
+            })
+    }
+}
+
+/// 打击音效文件（相对于谱面所在的文件夹），没有设置的判定不播放
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct HitSoundsToml {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perfect: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub great: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub good: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub miss: Option<String>,
+}
+
+impl HitSoundsToml {
+    pub fn is_empty(&self) -> bool {
+        self.perfect.is_none() && self.great.is_none() && self.good.is_none() && self.miss.is_none()
+    }
+}
+
+/// 谱面中的段落标记
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectionToml {
+    pub name: String,
+    /// 段落开始时间（秒）
+    pub time: f64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArrowTimeToml {
     pub click_time: f64,
     pub speed: Speed,
     pub direction: Directions,
+    /// 按键音名字（在 keysounds 中声明）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keysound: Option<String>,
 }
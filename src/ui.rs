@@ -1,8 +1,10 @@
 use bevy::prelude::*;
 
-use crate::consts::DELAY_SONG;
-use crate::score::ScoreResource;
+use crate::consts::{DELAY_SONG, WINDOW_WIDTH};
+use crate::gauge::{Gauge, GaugeType};
+use crate::score::{Player2Score, ScoreResource};
 use crate::time::ControlledTime;
+use crate::types::{Player, SongConfig};
 use crate::AppState;
 
 pub struct UIPlugin;
@@ -13,7 +15,8 @@ impl Plugin for UIPlugin {
             .add_system_set(
                 SystemSet::on_update(AppState::Game)
                     .with_system(update_time_text.system())
-                    .with_system(update_score_text.system()),
+                    .with_system(update_score_text.system())
+                    .with_system(update_gauge_bar.system()),
             )
             .add_system_set(SystemSet::on_exit(AppState::Game).with_system(despawn_text.system()));
     }
@@ -23,6 +26,7 @@ fn setup_ui(
     mut cmd: Commands,
     assert_server: Res<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    config: Res<SongConfig>,
 ) {
     let font: Handle<Font> = assert_server.load("fonts/FiraSans-Bold.ttf");
     let material = materials.add(Color::NONE.into());
@@ -58,36 +62,117 @@ fn setup_ui(
             })
             .insert(TimeText);
     });
-    // score text
-    cmd.spawn_bundle(NodeBundle {
-        style: Style {
-            position_type: PositionType::Absolute,
-            position: Rect {
-                left: Val::Px(10.0),
-                bottom: Val::Px(10.0),
+    // gauge，双人对战时不使用血条
+    if !config.versus {
+        cmd.spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexEnd,
                 ..Default::default()
             },
+            material: material.clone(),
             ..Default::default()
-        },
-        material,
-        ..Default::default()
-    })
-    .with_children(|parent| {
-        parent
-            .spawn_bundle(TextBundle {
-                text: Text::with_section(
-                    "Score: 0. Corrects: 0. Fails: 0.",
-                    TextStyle {
-                        font,
-                        font_size: 40.0,
-                        color: Color::rgb(0.8, 0.8, 0.8),
+        })
+        .insert(GaugeUI)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(GaugeText);
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        size: Size::new(Val::Px(GAUGE_WIDTH), Val::Px(GAUGE_HEIGHT)),
+                        ..Default::default()
                     },
-                    Default::default(),
-                ),
+                    material: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                                ..Default::default()
+                            },
+                            material: materials.add(Color::NONE.into()),
+                            ..Default::default()
+                        })
+                        .insert(GaugeFill);
+                });
+        });
+    }
+    // score text，双人对战时每个玩家在自己的半边显示分数
+    let players: &[Player] = if config.versus {
+        &Player::ALL
+    } else {
+        &[Player::One]
+    };
+    for player in players {
+        let position = match (config.versus, player) {
+            (true, Player::One) => Rect {
+                left: Val::Px(10.0),
+                top: Val::Px(60.0),
                 ..Default::default()
-            })
-            .insert(ScoreText);
-    });
+            },
+            _ => Rect {
+                left: Val::Px(10.0),
+                bottom: Val::Px(10.0),
+                ..Default::default()
+            },
+        };
+        cmd.spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position,
+                ..Default::default()
+            },
+            material: material.clone(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: 30.0,
+                            color: Color::rgb(0.8, 0.8, 0.8),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ScoreText(*player));
+        });
+    }
+    // 分开两个玩家的跑道
+    if config.versus {
+        cmd.spawn_bundle(SpriteBundle {
+            sprite: Sprite::new(Vec2::new(WINDOW_WIDTH, 2.0)),
+            material: materials.add(Color::rgba(0.8, 0.8, 0.8, 0.5).into()),
+            transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.5)),
+            ..Default::default()
+        })
+        .insert(LaneDivider);
+    }
 }
 struct TimeText;
 /// 更新时间文本
@@ -99,20 +184,85 @@ fn update_time_text(time: Res<ControlledTime>, mut time_text: Query<&mut Text, W
     }
 }
 
-struct ScoreText;
-/// 更新分数文本
+/// 玩家的分数文本
+struct ScoreText(Player);
+/// 双人对战时跑道之间的分隔线
+struct LaneDivider;
+/// 更新分数文本，双人对战时加上玩家名字
 fn update_score_text(
-    score_resource: Res<ScoreResource>,
-    mut score_text: Query<&mut Text, With<ScoreText>>,
+    score_1p: Res<ScoreResource>,
+    score_2p: Res<Player2Score>,
+    config: Res<SongConfig>,
+    score_text: Query<(&mut Text, &ScoreText, ChangeTrackers<ScoreText>)>,
 ) {
-    if score_resource.is_changed() {
-        let mut score_text = score_text.single_mut().unwrap();
-        score_text.sections[0].value = format!("{}", *score_resource);
+    score_text.for_each_mut(|(mut text, score_text, tracker)| {
+        let player = score_text.0;
+        let score = match player {
+            Player::One => &*score_1p,
+            Player::Two => &score_2p.0,
+        };
+        let changed = match player {
+            Player::One => score_1p.is_changed(),
+            Player::Two => score_2p.is_changed(),
+        };
+        if changed || tracker.is_added() {
+            text.sections[0].value = if config.versus {
+                format!("{}  {}", player.name(), score)
+            } else {
+                score.to_string()
+            };
+        }
+    });
+}
+
+/// 血条宽度
+const GAUGE_WIDTH: f32 = 300.0;
+/// 血条高度
+const GAUGE_HEIGHT: f32 = 20.0;
+
+struct GaugeUI;
+struct GaugeText;
+/// 血条中填充的部分
+struct GaugeFill;
+
+/// 更新血条长度和颜色，快要减到 0 时变红
+fn update_gauge_bar(
+    gauge: Res<Gauge>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    fill: Query<(&mut Style, &Handle<ColorMaterial>), With<GaugeFill>>,
+    text: Query<&mut Text, With<GaugeText>>,
+    added: Query<(), Added<GaugeFill>>,
+) {
+    if !(gauge.is_changed() || added.iter().next().is_some()) {
+        return;
     }
+    let color = if gauge.value < 0.3 && gauge.kind != GaugeType::NoFail {
+        Color::rgb(0.9, 0.25, 0.2)
+    } else {
+        match gauge.kind {
+            GaugeType::Normal => Color::rgb(0.3, 0.75, 0.4),
+            GaugeType::Hard | GaugeType::SuddenDeath => Color::rgb(0.9, 0.55, 0.2),
+            GaugeType::NoFail => Color::rgb(0.5, 0.5, 0.5),
+        }
+    };
+    fill.for_each_mut(|(mut style, material)| {
+        style.size.width = Val::Percent(gauge.value * 100.0);
+        if let Some(material) = materials.get_mut(material) {
+            material.color = color;
+        }
+    });
+    text.for_each_mut(|mut text| {
+        text.sections[0].value = format!("{} {:.0}%", gauge.kind.name(), gauge.value * 100.0);
+    });
 }
 
-type TimeOrScoreText = Or<(With<TimeText>, With<ScoreText>)>;
+type HudEntities = Or<(
+    With<TimeText>,
+    With<ScoreText>,
+    With<GaugeUI>,
+    With<LaneDivider>,
+)>;
 
-fn despawn_text(mut cmd: Commands, q: Query<Entity, TimeOrScoreText>) {
+fn despawn_text(mut cmd: Commands, q: Query<Entity, HudEntities>) {
     q.for_each(|e| cmd.entity(e).despawn_recursive());
 }
@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+
+use crate::chart::{load_chart, ChartPurpose};
+use crate::types::{Directions, Player};
+use crate::AppState;
+
+/// 双人对战：两个玩家各自选择同一首歌的谱面，开始后上下分屏游玩
+pub struct VersusPlugin;
+
+impl Plugin for VersusPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_enter(AppState::Versus).with_system(setup_versus_menu.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::Versus)
+                .with_system(versus_menu_keyboard.system())
+                .with_system(update_versus_menu_text.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Versus).with_system(despawn_versus_menu.system()),
+        );
+    }
+}
+
+/// 可以选择的谱面
+pub struct VersusChart {
+    /// 谱面文件（相对于 assets/songs）
+    pub chart_file: String,
+    /// 显示的名字，比如 "Song [Lv. 5]"
+    pub label: String,
+}
+
+/// 双人对战的谱面选择
+pub struct VersusSetup {
+    charts: Vec<VersusChart>,
+    /// 1P 和 2P 选择的谱面
+    selected: [usize; 2],
+}
+
+impl VersusSetup {
+    /// 两个玩家都从 selected 开始选择
+    pub fn new(charts: Vec<VersusChart>, selected: usize) -> Self {
+        Self {
+            charts,
+            selected: [selected; 2],
+        }
+    }
+    fn chart(&self, player: Player) -> &VersusChart {
+        &self.charts[self.selected[player as usize]]
+    }
+    fn select(&mut self, player: Player, step: isize) {
+        let len = self.charts.len() as isize;
+        let selected = &mut self.selected[player as usize];
+        *selected = (*selected as isize + step).rem_euclid(len) as usize;
+    }
+}
+
+struct VersusMenuUI;
+/// 玩家选择的谱面的文字
+struct VersusChartText(Player);
+
+fn setup_versus_menu(
+    mut cmd: Commands,
+    asset_server: Res<AssetServer>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let font: Handle<Font> = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text = |value: &str, font_size: f32| TextBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(8.0)),
+            ..Default::default()
+        },
+        text: Text::with_section(
+            value,
+            TextStyle {
+                font: font.clone(),
+                font_size,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    };
+    cmd.spawn_bundle(NodeBundle {
+        style: Style {
+            size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+            flex_direction: FlexDirection::ColumnReverse,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..Default::default()
+        },
+        material: materials.add(Color::NONE.into()),
+        ..Default::default()
+    })
+    .insert(VersusMenuUI)
+    .with_children(|parent| {
+        parent.spawn_bundle(text("Versus", 40.0));
+        for player in Player::ALL.iter() {
+            parent
+                .spawn_bundle(text("", 24.0))
+                .insert(VersusChartText(*player));
+        }
+        parent.spawn_bundle(text(
+            "1P plays with WASD, 2P with the arrow keys\n\
+             W/S and Up/Down: choose chart  Enter: start",
+            16.0,
+        ));
+    });
+}
+
+/// 1P 用 W/S、2P 用上下键选择谱面，回车开始
+fn versus_menu_keyboard(
+    mut cmd: Commands,
+    key_input: Res<Input<KeyCode>>,
+    mut setup: ResMut<VersusSetup>,
+    mut state: ResMut<State<AppState>>,
+    asset_server: Res<AssetServer>,
+) {
+    for player in Player::ALL.iter() {
+        if key_input.just_pressed(player.key(Directions::Up)) {
+            setup.select(*player, -1);
+        }
+        if key_input.just_pressed(player.key(Directions::Down)) {
+            setup.select(*player, 1);
+        }
+    }
+    if key_input.just_pressed(KeyCode::Return) {
+        let rival = setup.chart(Player::Two).chart_file.clone();
+        let purpose = ChartPurpose::versus(rival, &asset_server);
+        let chart_file = setup.chart(Player::One).chart_file.clone();
+        load_chart(&mut cmd, &mut state, &asset_server, chart_file, purpose);
+    }
+}
+
+fn update_versus_menu_text(
+    setup: Res<VersusSetup>,
+    added: Query<(), Added<VersusChartText>>,
+    q: Query<(&mut Text, &VersusChartText)>,
+) {
+    if !(setup.is_changed() || added.iter().next().is_some()) {
+        return;
+    }
+    q.for_each_mut(|(mut text, chart_text)| {
+        let player = chart_text.0;
+        text.sections[0].value = format!("{}: {}", player.name(), setup.chart(player).label);
+    });
+}
+
+fn despawn_versus_menu(mut cmd: Commands, q: Query<Entity, With<VersusMenuUI>>) {
+    q.for_each(|e| cmd.entity(e).despawn_recursive());
+}